@@ -34,7 +34,8 @@
 //! ```
 //!
 //! With the above call, you will map the `s` key in [`User`] [`Mode`]
-//! to the [`Sneak`] mode, you can also do that manually:
+//! to the [`Sneak`] mode, and the `S` key to a [reversed] version of
+//! it, you can also do that manually:
 //!
 //! ```rust
 //! setup_duat!(setup);
@@ -42,6 +43,7 @@
 //!
 //! fn setup() {
 //!     map::<User>("s", duat_sneak::Sneak::new());
+//!     map::<User>("S", duat_sneak::Sneak::new().reversed());
 //! }
 //! ```
 //!
@@ -53,16 +55,34 @@
 //!   If there are multiple instances, one entry will be selected, and
 //!   typing does the following:
 //!
-//!   - `n` for the next entry
+//!   - `n` (or `s`) for the next entry
 //!   - `N` for the previous entry if [`mode::alt_is_reverse()`] is
 //!     `false`
 //!   - `<A-n>` for the previous entry if [`mode::alt_is_reverse()`]
 //!     is `true`
+//!   - A count typed before `n` or `N` will skip that many entries
+//!   - Holding `<Ctrl>` will skip 5 entries at a time
+//!   - `<Down>` and `<Right>` for the next entry, `<Up>` and `<Left>`
+//!     for the previous one
+//!   - `g` for the first entry and `G` for the last one
+//!   - `<Backspace>` to type more characters, finished by `<Enter>`
+//!   - `<C-Space>` to collect the entry, which will also be selected
+//!   - `<C-a>` to select every entry
+//!   - `<C-r>` to place the caret on the start of the entry instead
+//!   - `<Enter>` will confirm the selected entry
 //!   - Any other key will select and return to the [default mode]
 //!
-//! - Any other key will pick the last `{char0}{char1}` sequence and
-//!   use that. If there was no previous sequence, just returns to the
-//!   [default mode].
+//! - `<Backspace>` while typing the sequence will remove its last
+//!   character, while `<Up>` and `<Down>` will go through the previous
+//!   sequences, which are confirmed with `<Enter>`.
+//!
+//! - Any other key will pick the last `{char0}{char1}` sequence in the
+//!   buffer and use that. If there was no previous sequence, just
+//!   returns to the [default mode].
+//!
+//! - `<Esc>` or `<C-c>`, at any point, will return to the [default
+//!   mode] without moving the selections. So will any character typed
+//!   with `<Ctrl>` or `<Alt>` while typing the sequence.
 //!
 //! # More Options
 //!
@@ -83,6 +103,22 @@
 //! previous entry and `,` selects the next. Additionally, this will
 //! select three characters instead of just two.
 //!
+//! You can also change where [`Sneak`] looks for matches, through the
+//! [`Scope`] enum:
+//!
+//! ```rust
+//! setup_duat!(setup);
+//! use duat::prelude::*;
+//! use duat_sneak::{Scope, Sneak};
+//!
+//! fn setup() {
+//!     map::<User>("f", Sneak::new().with_len(1).scope(Scope::Line));
+//! }
+//! ```
+//!
+//! The above mapping makes `f` behave like vim's `f`, only finding
+//! characters in the current line.
+//!
 //! # Labels
 //!
 //! If there are too many matches, switching to a far away match could
@@ -99,17 +135,97 @@
 //! ```
 //!
 //! Now, if there are 8 or more matches, instead of switching to them
-//! via `n` and `N`, labels with one character will show up on each
-//! match. If you type the character in a label, all other labels will
-//! be filtered out, until there is only one label left, at which
-//! point it will be selected and you'll return to the [default mode].
+//! via `n` and `N`, a label will show up on each match. If you type
+//! the characters in a label, its match will be selected and you'll
+//! return to the [default mode]. The closest matches get the first
+//! labels. If there are more matches than label characters, some
+//! labels will have two (or more) characters, and typing the first
+//! one will hide all labels that don't start with it. Typing the last
+//! character of a label in uppercase will extend the main selection
+//! to its match, instead of moving it, and `<Backspace>` will undo
+//! the last typed character of a label. `<Enter>` will select the
+//! first remaining label's match.
+//!
+//! Typing a label with `<Alt>` will instead add a new selection on
+//! its match, letting you keep adding selections until you press
+//! `<Esc>` or `<Enter>`, which won't move the main selection then.
+//!
+//! Clicking on a match (or its label) will also select it, while
+//! clicking anywhere else will leave [`Sneak`].
+//!
+//! Even without `min_for_labels`, pressing `<Tab>` while moving
+//! between matches with `n` and `N` will label them, and `<S-Tab>`
+//! will go back to moving.
+//!
+//! Labels make use of the lowercase letters, then the digits, and
+//! then `;,./'`. You can change which characters are used, and in
+//! what order, with [`Sneak::label_chars`]:
+//!
+//! ```rust
+//! setup_duat!(setup);
+//! use duat::prelude::*;
+//! use duat_sneak::Sneak;
+//!
+//! fn setup() {
+//!     let sneak = Sneak::new().min_for_labels(8).label_chars("asdfghjklqwertyuiopzxcvbnm");
+//!     map::<User>("s", sneak);
+//! }
+//! ```
+//!
+//! Characters used by the [selection keys] are never used as labels,
+//! and the characters that come right after the matches are only
+//! used if there are no other options, so you won't select a label by
+//! accident if you keep typing the pattern.
+//!
+//! # Repeating
+//!
+//! The [`SneakRepeat`] mode selects the next (or previous) match of
+//! the last pattern in the whole [`Buffer`], without highlighting
+//! anything. When plugging [`Sneak`], `;` is mapped to repeat it in
+//! the same direction and `,` in the opposite one, in [`User`] mode.
+//!
+//! Each [`Buffer`] repeats the last pattern searched in it, unless
+//! [`Sneak::global_last`] is set. If nothing was searched in it yet,
+//! the last pattern searched anywhere is used. The last patterns are
+//! also kept when Duat is closed or reloaded, so repeating right
+//! after it opens again will use them, and you can recall them with
+//! `<Up>`.
+//!
+//! # Commands
+//!
+//! When plugging [`Sneak`], the `sneak` command is also added. It
+//! enters [`Sneak`] with its argument as the pattern, as if it had
+//! been typed, and searches in the whole [`Buffer`] with the
+//! `--buffer` flag:
+//!
+//! ```text
+//! sneak --buffer fn
+//! ```
 //!
 //! # Forms
 //!
-//! When plugging [`Sneak`] this crate sets two [`Form`]s:
+//! When plugging [`Sneak`] (or when first using it) this crate sets
+//! the following [`Form`]s:
 //!
 //! - `"sneak.match"`, which is set to `"default.info"`
+//! - `"sneak.match.ahead"`, for matches after the cursor, which is set
+//!   to `"sneak.match"`
+//! - `"sneak.match.behind"`, for matches before the cursor, which is
+//!   set to `"sneak.match"`
+//! - `"sneak.match.filtered"`, for matches whose labels were filtered
+//!   out, which is set to `"default.info"`, but dimmed
 //! - `"sneak.label"`, which is set to `"accent.info"`
+//! - `"sneak.hint"`, for the next character of each match, which is
+//!   set to `"default.info"`, but dimmed
+//! - `"sneak.label.near"` and `"sneak.label.far"`, for labels close to
+//!   and far from the cursor, which are set to `"sneak.label"`. What
+//!   counts as near and far is set by [`Sneak::label_distance_tiers`]
+//! - `"sneak.count"`, for the number of labels left after typing part
+//!   of a label, which is set to `"default.info"`
+//! - `"sneak.current"`, for the match that would be selected, which is
+//!   set to `"default.info"`, but reversed
+//! - `"sneak.selected"`, for the collected matches, which is set to
+//!   `"sneak.current"`
 //!
 //! [`Mode`]: duat::mode::Mode
 //! [`vim-sneak`]: https://github.com/justinmk/vim-sneak
@@ -117,30 +233,102 @@
 //! [map]: https://docs.rs/duat/latest/duat/prelude/map
 //! [`User`]: duat::mode::User
 //! [default mode]: mode::reset
+//! [reversed]: Sneak::reversed
+//! [selection keys]: Sneak::select_keys
 use std::{
+    collections::HashMap,
     ops::Range,
-    sync::{LazyLock, Mutex},
+    sync::{
+        LazyLock, Mutex, Once,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use duat::{
     Plugin, Plugins,
-    mode::{KeyCode::*, KeyMod},
+    hook::{BufferClosed, BufferUpdated, ConfigUnloaded, OnMouseEvent},
+    mode::{KeyCode::*, KeyMod, MouseButton, MouseEventKind},
     prelude::*,
+    text::{Conceal, Inlay, RawTag, TwoPoints},
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 static NS: LazyLock<Ns> = Ns::new_lazy();
 static CUR_NS: LazyLock<Ns> = Ns::new_lazy();
-static LAST: Mutex<String> = Mutex::new(String::new());
+static SEL_NS: LazyLock<Ns> = Ns::new_lazy();
+static HISTORY: Mutex<Vec<Search>> = Mutex::new(Vec::new());
+const HISTORY_LEN: usize = 20;
+const SELECT_ALL_CAP: usize = 100;
+const HISTORY_CACHE: &str = "duat-sneak/history";
+static LAST_TARGET: Mutex<Option<(Handle<dyn Widget>, Range<usize>, u64)>> = Mutex::new(None);
+static ORIGIN: Mutex<Option<(Handle<dyn Widget>, mode::Selection)>> = Mutex::new(None);
+static LAST: Mutex<Vec<(Handle<dyn Widget>, Search)>> = Mutex::new(Vec::new());
+/// The [`Match`]es that can be clicked, and how to jump to them
+static TARGETS: Mutex<(Vec<Match>, Target, bool)> =
+    Mutex::new((Vec::new(), Target::SelectMatch, false));
+static KEYS_SENT: AtomicUsize = AtomicUsize::new(0);
+static PLUGGED: AtomicBool = AtomicBool::new(false);
+static DEADLINE: Mutex<Option<(Instant, usize)>> = Mutex::new(None);
+static REMOTE_NS: LazyLock<Ns> = Ns::new_lazy();
+static CLOAK_NS: LazyLock<Ns> = Ns::new_lazy();
+static REMOTE: Mutex<Option<Remote>> = Mutex::new(None);
 
 /// A [`Mode`] used for jumping to sequences of characters
 #[derive(Clone)]
 pub struct Sneak {
     step: Step,
-    len: usize,
-    prev_key: KeyEvent,
+    pattern: PatternOpts,
+    prev_key: Option<KeyEvent>,
     next_key: KeyEvent,
+    repeat_key: KeyEvent,
+    first_key: KeyEvent,
+    last_key: KeyEvent,
+    line_keys: Option<(KeyEvent, KeyEvent)>,
+    to_labels_key: KeyEvent,
+    to_move_key: KeyEvent,
+    refine_key: KeyEvent,
     min_for_labels: usize,
-    is_case_sensitive: bool,
+    stride: usize,
+    search: SearchOpts,
+    all_windows: bool,
+    directional_labels: bool,
+    passthrough_exit: bool,
+    arrow_keys: bool,
+    wraps: bool,
+    auto_jump: bool,
+    stay_on_no_match: bool,
+    confirm_after: Option<Duration>,
+    translations: HashMap<char, char>,
+    labels: LabelOpts,
+    handles: Vec<Handle<dyn Widget>>,
+    eager: Vec<(char, Match)>,
+    recalled: Option<(usize, PatternOpts)>,
+    global_last: bool,
+    prefilled: Option<String>,
+    origin: Option<mode::Selection>,
+    extends: bool,
+    target: Target,
+    all_selections: bool,
+    drops_unmatched: bool,
+    add_key: KeyEvent,
+    collected: Vec<Match>,
+    /// Wether selections were added with `<A-label>`
+    has_added: bool,
+    select_all_key: KeyEvent,
+    is_confirming_all: bool,
+    swap_key: KeyEvent,
+    operator_keys: Option<(KeyEvent, KeyEvent, KeyEvent)>,
+    insert_key: KeyEvent,
+    is_remote: bool,
+    between: Option<bool>,
+    first: Option<Range<usize>>,
+    column_key: Option<KeyEvent>,
+    cloak: Option<String>,
+    cloaked: Vec<(Handle<dyn Widget>, Range<usize>)>,
+    priorities: (u8, u8, u8),
+    shows_key_hints: bool,
 }
 
 impl Sneak {
@@ -148,18 +336,118 @@ impl Sneak {
     pub fn new() -> Self {
         Self {
             step: Step::Start,
-            len: 2,
-            next_key: KeyCode::Char('n').into(),
-            prev_key: if mode::alt_is_reverse() {
-                KeyEvent::new(KeyCode::Char('n'), KeyMod::ALT)
-            } else {
-                Char('N').into()
+            pattern: PatternOpts {
+                len: 2,
+                case: Case::Insensitive,
+                is_regex: false,
+                is_whole_word: false,
+                equivalences: Vec::new(),
+                space_matches_whitespace: true,
+                is_multiline: false,
+                ignore_accents: false,
             },
+            next_key: KeyCode::Char('n').into(),
+            prev_key: None,
+            repeat_key: Char('s').into(),
+            first_key: Char('g').into(),
+            last_key: Char('G').into(),
+            line_keys: None,
+            to_labels_key: Tab.into(),
+            to_move_key: BackTab.into(),
+            refine_key: Backspace.into(),
             min_for_labels: usize::MAX,
-            is_case_sensitive: false,
+            stride: 5,
+            search: SearchOpts {
+                scope: Scope::Screen,
+                is_reversed: false,
+                is_forward_only: false,
+                vertical_scope: None,
+                include_concealed: false,
+                skip_selected: true,
+                include_offscreen_columns: false,
+                at_word_start: false,
+                excluded_captures: Vec::new(),
+                chars: Chars::Any,
+            },
+            all_windows: false,
+            directional_labels: false,
+            passthrough_exit: false,
+            arrow_keys: true,
+            wraps: true,
+            auto_jump: true,
+            stay_on_no_match: false,
+            confirm_after: None,
+            translations: HashMap::new(),
+            labels: LabelOpts {
+                chars: "abcdefghijklmnopqrstuvwxyz0123456789;,./'".chars().collect(),
+                position: LabelPosition::Over,
+                distance_tiers: (5, 20),
+                is_eager: false,
+                has_hints: false,
+                is_paged: false,
+                is_only: false,
+                max: usize::MAX,
+                priority: 239,
+            },
+            handles: Vec::new(),
+            eager: Vec::new(),
+            recalled: None,
+            global_last: false,
+            prefilled: None,
+            origin: None,
+            extends: false,
+            target: Target::SelectMatch,
+            all_selections: false,
+            drops_unmatched: false,
+            add_key: KeyEvent::new(Char(' '), KeyMod::CONTROL),
+            collected: Vec::new(),
+            has_added: false,
+            select_all_key: KeyEvent::new(Char('a'), KeyMod::CONTROL),
+            is_confirming_all: false,
+            swap_key: KeyEvent::new(Char('r'), KeyMod::CONTROL),
+            operator_keys: None,
+            insert_key: Char('i').into(),
+            is_remote: false,
+            between: None,
+            first: None,
+            column_key: None,
+            cloak: Some("cloak".to_string()),
+            cloaked: Vec::new(),
+            priorities: (239, 239, 240),
+            shows_key_hints: true,
         }
     }
 
+    /// The options for labels, with only the usable `char`s
+    ///
+    /// A `char` is left out if it already does something while the
+    /// labels are shown, so a key always has only one meaning. These
+    /// are the [selection keys] (unless in [`labels_only`]), `' '`
+    /// when [paging labels], and uppercase `char`s, which are used to
+    /// extend the selection.
+    ///
+    /// [selection keys]: Self::select_keys
+    /// [`labels_only`]: Self::labels_only
+    /// [paging labels]: Self::paged_labels
+    fn label_opts(&self) -> LabelOpts {
+        let chars = self
+            .labels
+            .chars
+            .iter()
+            .copied()
+            .filter(|&char| {
+                let key: KeyEvent = Char(char).into();
+                let is_select_key = key == self.next_key || key == self.prev_key();
+
+                (self.labels.is_only || !is_select_key)
+                    && !(self.labels.is_paged && char == ' ')
+                    && !char.is_uppercase()
+            })
+            .collect();
+
+        LabelOpts { chars, priority: self.priorities.1, ..self.labels.clone() }
+    }
+
     /// Which `char`s to select the previous and next matches,
     /// respectively
     ///
@@ -170,263 +458,2709 @@ impl Sneak {
     ///   `false`
     /// - `<A-n>` for the previous entry if [`mode::alt_is_reverse()`]
     ///   is `true`
+    #[track_caller]
     pub fn select_keys(self, prev: char, next: char) -> Self {
-        Self {
-            prev_key: Char(prev).into(),
-            next_key: Char(next).into(),
-            ..self
-        }
+        self.select_key_events(Char(prev).into(), Char(next).into())
     }
 
-    /// Sneaks with `len` chars, as opposed to just 2
+    /// Like [`select_keys`], but with any [`KeyEvent`]
+    ///
+    /// This lets you use keys with modifiers, like `<A-;>` and
+    /// `<A-,>`:
+    ///
+    /// ```rust
+    /// # use duat::mode::{KeyCode::Char, KeyEvent, KeyMod};
+    /// # use duat_sneak::Sneak;
+    /// let sneak = Sneak::new().select_key_events(
+    ///     KeyEvent::new(Char(','), KeyMod::ALT),
+    ///     KeyEvent::new(Char(';'), KeyMod::ALT),
+    /// );
+    /// ```
+    ///
+    /// [`select_keys`]: Self::select_keys
     #[track_caller]
-    pub fn with_len(self, len: usize) -> Self {
-        assert!(len >= 1, "Can't match on 0 characters");
-        Self { len, ..self }
+    pub fn select_key_events(self, prev: KeyEvent, next: KeyEvent) -> Self {
+        assert!(prev != next, "The previous and next keys must be different");
+        Self { prev_key: Some(prev), next_key: next, ..self }
     }
 
-    /// Sneak with or without caring for case-insensitivity
-    pub fn with_case_sensitivity(self, case_sensitivity: bool) -> Self {
-        Self {
-            is_case_sensitive: case_sensitivity,
-            ..self
-        }
+    /// The key to select the previous match
+    ///
+    /// If it wasn't set, depends on [`mode::alt_is_reverse()`] at the
+    /// time it is pressed.
+    fn prev_key(&self) -> KeyEvent {
+        self.prev_key.unwrap_or_else(|| {
+            if mode::alt_is_reverse() {
+                KeyEvent::new(Char('n'), KeyMod::ALT)
+            } else {
+                Char('N').into()
+            }
+        })
     }
 
-    /// Sets a minimum number of matches to enable labels
+    /// A `char` that also selects the next match
     ///
-    /// Instead of getting to a specific match with [the selection
-    /// keys], a label will appear in front of each match, if you type
-    /// the character in the label, [`Sneak`] will filter out all non
-    /// matching labels until there are only at most 26 left, in which
-    /// case the next character will finish sneaking.
+    /// By default, this is `s`, so if [`Sneak`] is mapped to `s`,
+    /// you can keep pressing it to move through the matches.
+    pub fn repeat_key(self, repeat: char) -> Self {
+        Self { repeat_key: Char(repeat).into(), ..self }
+    }
+
+    /// Key to collect the current match while moving between them
     ///
-    /// This feature is disabled by default (i.e. `min_for_labels ==
-    /// usize::MAX`).
+    /// By default, this is `<C-Space>`. Collected matches are
+    /// highlighted with `"sneak.selected"`, and pressing it again
+    /// on one of them will drop it. When a match is selected, every
+    /// collected match also gets a selection, while leaving with
+    /// `<Esc>` discards them.
+    pub fn add_key(self, add_key: KeyEvent) -> Self {
+        Self { add_key, ..self }
+    }
+
+    /// Key to select every match at once
     ///
-    /// [the selection keys]: Self::select_keys
-    pub fn min_for_labels(self, min_for_labels: usize) -> Self {
-        Self { min_for_labels, ..self }
+    /// By default, this is `<C-a>`, which works both while moving
+    /// between the matches and while they're labeled. The main
+    /// selection is placed on the match closest to it. If there are
+    /// more than 100 matches, the key has to be pressed twice.
+    pub fn select_all_key(self, select_all_key: KeyEvent) -> Self {
+        Self { select_all_key, ..self }
     }
-}
 
-impl Plugin for Sneak {
-    fn plug(self, _: &mut Opts, _: &Plugins) {
-        use Step::*;
-        let cloak_ns = Ns::new();
+    /// Key to swap the anchor and caret of the selected match
+    ///
+    /// By default, this is `<C-r>`. Pressing it while moving between
+    /// the matches or while they're labeled switches between
+    /// [`Target::SelectMatch`] and [`Target::SelectMatchSwapped`],
+    /// so the caret is placed on the other end of the match.
+    pub fn swap_key(self, swap_key: KeyEvent) -> Self {
+        Self { swap_key, ..self }
+    }
 
-        hook::add::<ModeSwitched>(move |pa, mut switch| {
-            if switch.new.is::<Sneak>() {
-                let buffer = context::current_buffer(pa);
+    /// Which `char`s to select the first and last matches,
+    /// respectively
+    ///
+    /// By default, they are `g` and `G`.
+    pub fn edge_keys(self, first: char, last: char) -> Self {
+        Self {
+            first_key: Char(first).into(),
+            last_key: Char(last).into(),
+            ..self
+        }
+    }
 
-                let id = form::id_of!("cloak");
-                buffer.text_mut(pa).insert_tag(cloak_ns, .., id.to_tag(239));
-            } else if let Some(sneak) = switch.old.get_as::<Sneak>() {
-                let buffer = context::current_buffer(pa);
+    /// Keys to select the nearest match on a line above and below
+    ///
+    /// Other matches on the same line as the current one are skipped,
+    /// and the match closest to its column is picked. By default,
+    /// there are no such keys.
+    pub fn line_keys(self, above: KeyEvent, below: KeyEvent) -> Self {
+        Self { line_keys: Some((above, below)), ..self }
+    }
 
-                if let Filter(pat) | MatchedMove(pat, ..) | MatchedLabels(pat, _) = &sneak.step {
-                    *LAST.lock().unwrap() = pat.clone();
-                }
+    /// Key to also select every match in the same column
+    ///
+    /// When pressed while moving between the matches, the current
+    /// match is selected, and a selection is added on every other
+    /// match that starts on the same column, which is useful for
+    /// aligned text, like tables. Matches on the same line as
+    /// another selection are skipped. By default, there is no such
+    /// key.
+    pub fn column_key(self, column_key: KeyEvent) -> Self {
+        Self { column_key: Some(column_key), ..self }
+    }
 
-                let mut text = buffer.text_mut(pa);
-                text.remove_tags(*NS, ..);
-                text.remove_tags(*CUR_NS, ..);
-                text.remove_tags(cloak_ns, ..);
-            }
-        });
+    /// Keys to delete, change and yank up to the current match
+    ///
+    /// While moving between the matches, these act on the text from
+    /// the main caret to the match, including the character that the
+    /// [`Target`] would place the caret on. So [`Target::Before`]
+    /// leaves the match itself untouched, like vim's `dt`.
+    ///
+    /// Changing deletes the text and then sends the
+    /// [`insert_key`] to the [default mode]. By default, there
+    /// are no such keys, since any other key selects the match.
+    ///
+    /// [`insert_key`]: Self::insert_key
+    /// [default mode]: mode::reset
+    pub fn operator_keys(self, delete: char, change: char, yank: char) -> Self {
+        let operator_keys = Some((Char(delete).into(), Char(change).into(), Char(yank).into()));
+        Self { operator_keys, ..self }
+    }
 
-        mode::map::<mode::User>("s", move |pa: &mut Pass| mode::set(pa, self.clone()))
-            .doc(txt!("Enter [mode]Sneak[] mode"));
+    /// Key sent to the [default mode] after changing text
+    ///
+    /// By default, this is `i`, which should enter some sort of
+    /// insert mode. See [`operator_keys`] for more information.
+    ///
+    /// [default mode]: mode::reset
+    /// [`operator_keys`]: Self::operator_keys
+    pub fn insert_key(self, insert_key: KeyEvent) -> Self {
+        Self { insert_key, ..self }
+    }
 
-        form::set_weak("sneak.match", Form::mimic("default.info"));
-        form::set_weak("sneak.label", Form::mimic("accent.info"));
-        form::set_weak("sneak.current", Form::new().underlined());
+    /// How many matches to skip when pressing the selection keys
+    /// with `<Ctrl>`
+    ///
+    /// By default, this is `5`, so `<C-n>` will skip to the fifth
+    /// next match. A count before the key multiplies the stride.
+    pub fn stride(self, stride: usize) -> Self {
+        assert!(stride >= 1, "Can't skip 0 matches");
+        Self { stride, ..self }
     }
-}
 
-impl Mode for Sneak {
-    fn bindings() -> mode::Bindings {
-        mode::bindings!(match _ {
-            unmod!(Char(..)) => txt!("Filter by [key.char]{{char}}"),
-        })
+    /// Starts with a pattern already typed
+    ///
+    /// When entering [`Sneak`], the matches for `pat` will be found
+    /// right away, as if it had been typed. If `pat` is shorter than
+    /// the [length], the remaining `char`s can still be typed.
+    ///
+    /// ```rust
+    /// setup_duat!(setup);
+    /// use duat::prelude::*;
+    /// use duat_sneak::Sneak;
+    ///
+    /// fn setup() {
+    ///     map::<User>(":", Sneak::new().with_pattern("::"));
+    /// }
+    /// ```
+    ///
+    /// [length]: Self::with_len
+    pub fn with_pattern(self, pat: impl ToString) -> Self {
+        let pat = pat.to_string();
+        Self { prefilled: (!pat.is_empty()).then_some(pat), ..self }
     }
 
-    fn send_key(&mut self, pa: &mut Pass, key: mode::KeyEvent) {
-        let widget = context::current_widget(pa);
+    /// A [`Sneak`] that stops right before a `char`, like vim's `t`
+    ///
+    /// This is the same as `Sneak::new().with_len(1)` with
+    /// [`Target::Before`]. Repeating it with [`SneakRepeat`] skips
+    /// the match right after the caret, so it doesn't get stuck.
+    pub fn till() -> Self {
+        Self::new().with_len(1).target(Target::Before)
+    }
 
-        match &mut self.step {
-            Step::Start => {
-                let (pat, finished_filtering) = if let unmod!(Char(char)) = key {
-                    (char.to_string(), self.len == 1)
-                } else {
-                    let last = LAST.lock().unwrap();
+    /// A [`Sneak`] that stops right after a previous `char`, like
+    /// vim's `T`
+    ///
+    /// This is the backwards counterpart of [`Sneak::till`].
+    pub fn till_backward() -> Self {
+        Self::new().reversed().with_len(1).target(Target::After)
+    }
 
-                    if last.is_empty() {
-                        context::error!("mode hasn't been set to [a]Sneak[] yet");
-                        mode::reset::<Buffer>(pa);
-                        return;
-                    } else {
-                        (last.clone(), true)
-                    }
-                };
+    /// Sneaks with `len` chars, as opposed to just 2
+    #[track_caller]
+    pub fn with_len(self, len: usize) -> Self {
+        assert!(len >= 1, "Can't match on 0 characters");
+        let pattern = PatternOpts { len, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                let should_ci = if self.is_case_sensitive { "" } else { "(?i)" };
-                let regex = format!(
-                    "{should_ci}{pat}[^\n]{{{}}}",
-                    self.len - pat.chars().count()
-                );
-                let (matches, cur) = hi_matches(pa, &regex, &widget);
+    /// Sneak with or without caring for case-insensitivity
+    pub fn with_case_sensitivity(self, case_sensitivity: bool) -> Self {
+        let case = if case_sensitivity {
+            Case::Sensitive
+        } else {
+            Case::Insensitive
+        };
+        let pattern = PatternOpts { case, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                let Some(cur) = cur else {
-                    context::error!("No matches found for [a]{pat}");
-                    mode::reset::<Buffer>(pa);
-                    return;
-                };
+    /// Sneak without caring for case
+    ///
+    /// This is the default, and is the same as
+    /// [`with_case_sensitivity(false)`]. It also applies when
+    /// repeating the last pattern.
+    ///
+    /// [`with_case_sensitivity(false)`]: Self::with_case_sensitivity
+    pub fn ignore_case(self) -> Self {
+        self.with_case_sensitivity(false)
+    }
 
-                self.step = if finished_filtering {
-                    // Stop immediately if there is only one match
-                    if matches.len() == 1 {
-                        let range = matches[0].clone();
-                        widget.edit_main(pa, |mut c| c.move_to(range));
+    /// Sneak like vim's `smartcase`
+    ///
+    /// Matching will be case-insensitive while the typed pattern is
+    /// all lowercase, and case-sensitive as soon as it contains an
+    /// uppercase character.
+    pub fn smart_case(self) -> Self {
+        let pattern = PatternOpts { case: Case::Smart, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                        mode::reset::<Buffer>(pa);
+    /// Treat the typed characters as a regex
+    ///
+    /// Instead of typing a fixed number of characters, the pattern
+    /// is finished by pressing `<Enter>`, and the matches will be
+    /// highlighted as you type. This lets you sneak to things like
+    /// `fn \w+\(`.
+    pub fn regex(self) -> Self {
+        let pattern = PatternOpts { is_regex: true, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                        Step::MatchedMove(pat, matches, cur)
-                    } else if matches.len() >= self.min_for_labels {
-                        hi_labels(pa, &widget, &matches);
+    /// Only match whole words
+    ///
+    /// The whole matched sequence (not just the typed characters)
+    /// must be surrounded by word boundaries, so sneaking to `in`
+    /// won't match the middle of `String`.
+    pub fn whole_word(self) -> Self {
+        let pattern = PatternOpts { is_whole_word: true, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                        Step::MatchedLabels(pat, matches)
-                    } else {
-                        hi_cur(pa, &widget, matches[cur].clone(), matches[cur].clone());
+    /// Only accept matches that start at the start of a word
+    ///
+    /// Word starts include `camelCase` humps and the characters after
+    /// underscores in `snake_case`. This drastically reduces the
+    /// number of matches on dense lines of code.
+    pub fn at_word_start(self) -> Self {
+        let search = SearchOpts { at_word_start: true, ..self.search };
+        Self { search, ..self }
+    }
 
-                        Step::MatchedMove(pat, matches, cur)
-                    }
-                } else {
-                    Step::Filter(pat)
-                }
-            }
-            Step::Filter(pat) => {
-                widget.text_mut(pa).remove_tags(*NS, ..);
+    /// Sets classes of equivalent characters
+    ///
+    /// When you type one of the `char`s, it will match any of the
+    /// `char`s in its class, like in [`vim-sneak`]:
+    ///
+    /// ```rust
+    /// setup_duat!(setup);
+    /// use duat::prelude::*;
+    /// use duat_sneak::Sneak;
+    ///
+    /// fn setup() {
+    ///     let sneak = Sneak::new().equivalences([('[', "[({<"), ('\'', "'\"`")]);
+    ///     map::<User>("s", sneak);
+    /// }
+    /// ```
+    ///
+    /// Repeating the last pattern will still use the typed `char`s.
+    ///
+    /// [`vim-sneak`]: https://github.com/justinmk/vim-sneak
+    pub fn equivalences<'a>(self, classes: impl IntoIterator<Item = (char, &'a str)>) -> Self {
+        let equivalences = classes
+            .into_iter()
+            .map(|(char, class)| (char, class.to_string()))
+            .collect();
+        let pattern = PatternOpts { equivalences, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                let (regex, finished_filtering) = if let unmod!(Char(char)) = key {
-                    pat.push(char);
+    /// Wether a typed space should match any horizontal whitespace
+    ///
+    /// This is `true` by default, so sneaking to `= {` will also
+    /// find `=\t{`.
+    pub fn space_matches_whitespace(self, space_matches_whitespace: bool) -> Self {
+        let pattern = PatternOpts { space_matches_whitespace, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                    let should_ci = if self.is_case_sensitive { "" } else { "(?i)" };
+    /// Wether matches can span multiple lines
+    ///
+    /// With this enabled, the characters after the typed ones may
+    /// be newlines, and pressing `<Enter>` while typing the pattern
+    /// will add a newline to it.
+    pub fn multiline(self, is_multiline: bool) -> Self {
+        let pattern = PatternOpts { is_multiline, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                    let regex = format!(
-                        "{should_ci}{pat}[^\n]{{{}}}",
-                        self.len - pat.chars().count()
-                    );
-                    (regex, pat.chars().count() >= self.len)
-                } else {
-                    (pat.clone(), true)
-                };
+    /// Match characters regardless of their accents
+    ///
+    /// Typing `e` will also match `é`, `ê`, `è` and so on, both in
+    /// their composed and decomposed forms. This composes with the
+    /// case options.
+    pub fn ignore_accents(self) -> Self {
+        let pattern = PatternOpts { ignore_accents: true, ..self.pattern };
+        Self { pattern, ..self }
+    }
 
-                let (matches, cur) = hi_matches(pa, &regex, &widget);
+    /// Translates typed characters before using them
+    ///
+    /// This is useful if you switch between keyboard layouts, since
+    /// you can map, for example, `ф` to `a`, and sneak without
+    /// switching back. The translations also apply to the labels and
+    /// the selection keys.
+    pub fn char_translations(self, map: impl Into<HashMap<char, char>>) -> Self {
+        Self { translations: map.into(), ..self }
+    }
 
-                let Some(cur) = cur else {
-                    context::error!("No matches found for [a]{pat}");
-                    mode::reset::<Buffer>(pa);
-                    return;
-                };
+    /// Sneak backwards, like `S` in [`vim-sneak`]
+    ///
+    /// Only matches before the cursor will be considered, and they
+    /// will be ordered from nearest to farthest, so the next key will
+    /// move towards the top of the screen.
+    ///
+    /// [`vim-sneak`]: https://github.com/justinmk/vim-sneak
+    pub fn reversed(self) -> Self {
+        let search = SearchOpts { is_reversed: true, ..self.search };
+        Self { search, ..self }
+    }
 
-                hi_cur(pa, &widget, matches[cur].clone(), matches[cur].clone());
+    /// Only sneak to matches after the cursor
+    ///
+    /// Normally, if there are no matches after the cursor, the last
+    /// match on screen will be selected. With this option, matches
+    /// before the cursor won't be highlighted at all, and cycling
+    /// through them will only wrap around the ones ahead.
+    pub fn forward_only(self) -> Self {
+        let search = SearchOpts { is_forward_only: true, ..self.search };
+        Self { search, ..self }
+    }
 
-                if finished_filtering {
-                    // Stop immediately if there is only one match
-                    self.step = if matches.len() == 1 {
-                        let range = matches[0].clone();
-                        widget.edit_main(pa, |mut c| c.move_to(range));
+    /// Where to look for matches
+    ///
+    /// By default, this is [`Scope::Screen`], which looks for matches
+    /// on the visible area. With [`Scope::Line`], only the line of the
+    /// main cursor will be searched, which, combined with
+    /// [`with_len(1)`], makes [`Sneak`] behave like vim's `f`.
+    ///
+    /// [`with_len(1)`]: Self::with_len
+    pub fn scope(self, scope: Scope) -> Self {
+        let search = SearchOpts { scope, ..self.search };
+        Self { search, ..self }
+    }
 
-                        mode::reset::<Buffer>(pa);
+    /// Wether to dim the text while the matches are highlighted
+    ///
+    /// By default, this is `true`, and the text is shown with the
+    /// `"cloak"` [`Form`] once the first `char` is typed. It is never
+    /// shown with [`Scope::Line`], nor if [`Sneak`] wasn't plugged,
+    /// since the plugin is what removes it.
+    pub fn cloak(self, cloaks: bool) -> Self {
+        let cloak = match (cloaks, self.cloak) {
+            (true, cloak) => cloak.or_else(|| Some("cloak".to_string())),
+            (false, _) => None,
+        };
+        Self { cloak, ..self }
+    }
 
-                        Step::MatchedMove(pat.clone(), matches, cur)
-                    } else if matches.len() >= self.min_for_labels {
-                        hi_labels(pa, &widget, &matches);
+    /// Which [`Form`] to dim the text with
+    ///
+    /// By default, this is `"cloak"`. This also enables the cloak if
+    /// it was disabled with [`cloak`].
+    ///
+    /// [`cloak`]: Self::cloak
+    pub fn cloak_form(self, name: impl ToString) -> Self {
+        Self { cloak: Some(name.to_string()), ..self }
+    }
 
-                        Step::MatchedLabels(pat.clone(), matches)
-                    } else {
-                        hi_cur(pa, &widget, matches[cur].clone(), matches[cur].clone());
+    /// The priorities of the cloak, the matches, and the current
+    /// match, respectively
+    ///
+    /// When [`Form`]s overlap, the one with the higher priority is
+    /// applied last, so it shows on top of the others. By default,
+    /// these are `239`, `239` and `240`, which is above what most
+    /// plugins use for things like syntax highlighting and matching
+    /// pairs. If another plugin's highlights are covering the
+    /// matches, you can raise these.
+    ///
+    /// The current match's priority should be higher than that of
+    /// the other matches, so it stands out.
+    pub fn priorities(self, cloak: u8, matches: u8, current: u8) -> Self {
+        Self { priorities: (cloak, matches, current), ..self }
+    }
 
-                        Step::MatchedMove(pat.clone(), matches, cur)
-                    };
-                }
-            }
-            Step::MatchedMove(_, matches, cur) => {
-                let prev = *cur;
-                let last = matches.len() - 1;
-
-                if key == self.next_key {
-                    *cur = if *cur == last { 0 } else { *cur + 1 };
-                    hi_cur(pa, &widget, matches[*cur].clone(), matches[prev].clone());
-                } else if key == self.prev_key {
-                    *cur = if *cur == 0 { last } else { *cur - 1 };
-                    hi_cur(pa, &widget, matches[*cur].clone(), matches[prev].clone());
-                } else {
-                    let range = matches[*cur].clone();
-                    widget.edit_main(pa, |mut c| c.move_to(range));
+    /// Only sneak to matches within `cols` columns of the cursor
+    ///
+    /// This is [`vim-sneak`]'s "vertical scope", and is useful for
+    /// jumping around column aligned text, like tables and struct
+    /// fields.
+    ///
+    /// [`vim-sneak`]: https://github.com/justinmk/vim-sneak
+    pub fn vertical_scope(self, cols: usize) -> Self {
+        let search = SearchOpts { vertical_scope: Some(cols), ..self.search };
+        Self { search, ..self }
+    }
 
-                    mode::reset::<Buffer>(pa);
-                }
-            }
-            Step::MatchedLabels(_, matches) => {
-                widget.text_mut(pa).remove_tags(*NS, ..);
+    /// Wether to include matches that are concealed
+    ///
+    /// By default, matches inside of concealed regions (e.g. closed
+    /// folds) are not considered, since jumping to them would give no
+    /// visual feedback.
+    pub fn include_concealed(self, include_concealed: bool) -> Self {
+        let search = SearchOpts { include_concealed, ..self.search };
+        Self { search, ..self }
+    }
 
-                let filtered_label = if let unmod!(Char(char)) = key
-                    && iter_labels(matches.len()).any(|label| char == label)
-                {
-                    char
-                } else {
-                    if let unmod!(Char(char)) = key {
-                        context::error!("[a]{char}[] is not a valid label");
-                    } else {
-                        context::error!("[a]{key.code:?}[] is not a valid label");
-                    }
-                    mode::reset::<Buffer>(pa);
-                    return;
-                };
+    /// Wether to skip matches that overlap with the selections
+    ///
+    /// This is `true` by default, so the text that is already
+    /// selected (including the character under the main cursor) won't
+    /// be highlighted or labeled. This has no effect on
+    /// [`Scope::Selection`], since all of its matches are selected.
+    ///
+    /// These matches are left out before anything else, so they
+    /// don't count towards [`min_for_labels`], don't take up any
+    /// labels and are never the first match selected by `n`. Set
+    /// this to `false` if you want to sneak to the text under the
+    /// cursor.
+    ///
+    /// [`min_for_labels`]: Self::min_for_labels
+    pub fn skip_selected(self, skip_selected: bool) -> Self {
+        let search = SearchOpts { skip_selected, ..self.search };
+        Self { search, ..self }
+    }
 
-                let mut iter = iter_labels(matches.len());
-                matches.retain(|_| iter.next() == Some(filtered_label));
+    /// Wether to include matches that are horizontally scrolled out
+    /// of view
+    ///
+    /// When wrapping is disabled, long lines may have matches that
+    /// are to the left or right of the visible area. By default,
+    /// these are not considered.
+    pub fn include_offscreen_columns(self, include_offscreen_columns: bool) -> Self {
+        let search = SearchOpts { include_offscreen_columns, ..self.search };
+        Self { search, ..self }
+    }
 
-                if matches.len() == 1 {
-                    let range = matches[0].clone();
-                    widget.edit_main(pa, |mut c| c.move_to(range));
+    /// Only accept matches made entirely of identifier characters
+    ///
+    /// These are letters, digits and underscores, from any script.
+    /// For example, with a length of 2, sneaking to `s` would match
+    /// the `se` in `self`, but not the `s.` in `s.len()`.
+    pub fn identifiers_only(self) -> Self {
+        let search = SearchOpts { chars: Chars::Identifiers, ..self.search };
+        Self { search, ..self }
+    }
 
-                    mode::reset::<Buffer>(pa);
-                } else {
-                    hi_labels(pa, &widget, matches);
-                }
-            }
-        }
+    /// Only accept matches made entirely of symbols
+    ///
+    /// This is the complement of [`identifiers_only`], so, for
+    /// example, with a length of 2, sneaking to `:` would only match
+    /// on `::` and `:>`, never on `a:`.
+    ///
+    /// [`identifiers_only`]: Self::identifiers_only
+    pub fn symbols_only(self) -> Self {
+        let search = SearchOpts { chars: Chars::Symbols, ..self.search };
+        Self { search, ..self }
     }
-}
 
-fn hi_labels(pa: &mut Pass, handle: &Handle<dyn Widget>, matches: &Vec<Range<usize>>) {
-    let mut text = handle.text_mut(pa);
+    /// Excludes matches inside of the given syntax captures
+    ///
+    /// This makes use of the [`Form`]s applied by syntax
+    /// highlighting, so a match will be dropped if it starts within
+    /// one of the captures, or one of their children (e.g.
+    /// `"comment"` also excludes `"comment.documentation"`):
+    ///
+    /// ```rust
+    /// setup_duat!(setup);
+    /// use duat::prelude::*;
+    /// use duat_sneak::Sneak;
+    ///
+    /// fn setup() {
+    ///     map::<User>("s", Sneak::new().exclude_captures(&["comment", "string"]));
+    /// }
+    /// ```
+    ///
+    /// If the [`Buffer`] has no syntax highlighting, this does
+    /// nothing.
+    ///
+    /// [`Form`]: duat::form::Form
+    pub fn exclude_captures(self, captures: &[&str]) -> Self {
+        let excluded_captures = captures.iter().map(|cap| cap.to_string()).collect();
+        let search = SearchOpts { excluded_captures, ..self.search };
+        Self { search, ..self }
+    }
 
-    text.remove_tags(*NS, ..);
-    text.remove_tags(*CUR_NS, ..);
+    /// Wether to look for matches in every [`Buffer`] on screen
+    ///
+    /// Matches are ordered starting from the current [`Buffer`], and
+    /// labels are shared between all of them. Selecting a match in
+    /// another [`Buffer`] will also focus on it.
+    pub fn all_windows(self, all_windows: bool) -> Self {
+        Self { all_windows, ..self }
+    }
+
+    /// Which `char`s to use as labels, in order of priority
+    ///
+    /// By default, these are the lowercase letters, followed by the
+    /// digits and `;,./'`. The `char`s used by the [selection keys]
+    /// will be left out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chars` is empty or has duplicate `char`s.
+    ///
+    /// [selection keys]: Self::select_keys
+    pub fn label_chars(self, chars: &str) -> Self {
+        let chars: Vec<char> = chars.chars().collect();
+        assert!(!chars.is_empty(), "Can't label with 0 characters");
+        for (i, char) in chars.iter().enumerate() {
+            assert!(!chars[..i].contains(char), "Duplicate label character {char:?}");
+        }
 
-    for (label, range) in iter_labels(matches.len()).zip(matches) {
-        let ghost = Overlay::new(txt!("[sneak.label:239]{label}"));
-        text.insert_tag(*NS, range.start, ghost);
+        let labels = LabelOpts { chars, ..self.labels };
+        Self { labels, ..self }
     }
-}
 
-fn hi_matches(pa: &mut Pass, pat: &str, widget: &Handle<dyn Widget>) -> (Vec<Range<usize>>, Option<usize>) {
+    /// Show labels as soon as the first character is typed
+    ///
+    /// Like in [`flash.nvim`], while you're still typing the pattern,
+    /// you can either keep typing or type a label to select its
+    /// match. Labels are only drawn from characters that can't
+    /// continue the pattern on any of the matches on screen, so the
+    /// pattern is always preferred.
+    ///
+    /// [`flash.nvim`]: https://github.com/folke/flash.nvim
+    pub fn eager_labels(self) -> Self {
+        let labels = LabelOpts { is_eager: true, ..self.labels };
+        Self { labels, ..self }
+    }
+
+    /// Use digits as the first labels, followed by the letters
+    ///
+    /// This is a preset for [`label_chars`], with `1` through `9`
+    /// followed by `a` through `z`. While the labels are shown,
+    /// digits are always treated as labels, never as counts.
+    ///
+    /// [`label_chars`]: Self::label_chars
+    pub fn numeric_labels(self) -> Self {
+        self.label_chars("123456789abcdefghijklmnopqrstuvwxyz")
+    }
+
+    /// Show the next character of each match while typing
+    ///
+    /// Like in [`leap.nvim`], once the first character is typed, a
+    /// hint with the following character will show up after each
+    /// match, so you can see which one leads to the match you want.
+    ///
+    /// [`leap.nvim`]: https://github.com/ggandor/leap.nvim
+    pub fn hint_next_char(self) -> Self {
+        let labels = LabelOpts { has_hints: true, ..self.labels };
+        Self { labels, ..self }
+    }
+
+    /// Where to place the labels
+    ///
+    /// By default, this is [`LabelPosition::Over`], which places
+    /// them over the first characters of the matches. With
+    /// [`LabelPosition::After`], they will be placed right after the
+    /// matches instead, so you can see the whole match.
+    pub fn label_position(self, position: LabelPosition) -> Self {
+        let labels = LabelOpts { position, ..self.labels };
+        Self { labels, ..self }
+    }
+
+    /// How many lines away from the caret a label stops being near,
+    /// and starts being far
+    ///
+    /// Labels that are less than `near` lines away from the main
+    /// caret use the `"sneak.label.near"` [`Form`], while those that
+    /// are `far` or more lines away use `"sneak.label.far"`. All
+    /// others use `"sneak.label"`. By default, these are `5` and
+    /// `20`.
+    ///
+    /// [`Form`]: duat::form::Form
+    pub fn label_distance_tiers(self, near: usize, far: usize) -> Self {
+        let labels = LabelOpts { distance_tiers: (near, far), ..self.labels };
+        Self { labels, ..self }
+    }
+
+    /// Splits labels in pages, instead of using longer labels
+    ///
+    /// When there are more matches than label characters, only the
+    /// closest matches will be labeled, and `<Space>` will move the
+    /// labels to the next group of matches, like in [`leap.nvim`].
+    /// `<S-Space>` and `<Backspace>` go back to the previous group.
+    ///
+    /// [`leap.nvim`]: https://github.com/ggandor/leap.nvim
+    pub fn paged_labels(self) -> Self {
+        let labels = LabelOpts { is_paged: true, ..self.labels };
+        Self { labels, ..self }
+    }
+
+    /// Only label the `max` matches closest to the cursor
+    ///
+    /// On dense screens, labeling every match just adds noise. The
+    /// matches that aren't labeled will still be highlighted, and
+    /// you can reach them by [switching] to the selection keys.
+    ///
+    /// [switching]: Self::switch_keys
+    pub fn max_labels(self, max: usize) -> Self {
+        let labels = LabelOpts { max, ..self.labels };
+        Self { labels, ..self }
+    }
+
+    /// Sets a minimum number of matches to enable labels
+    ///
+    /// Instead of getting to a specific match with [the selection
+    /// keys], a label will appear in front of each match, and typing
+    /// the characters in a label will select its match. When there
+    /// are too many matches, labels will have more than one
+    /// character, and typing the first ones will filter out all non
+    /// matching labels.
+    ///
+    /// This feature is disabled by default (i.e. `min_for_labels ==
+    /// usize::MAX`). If [`labels_only`] is set, this option is
+    /// ignored.
+    ///
+    /// [the selection keys]: Self::select_keys
+    /// [`labels_only`]: Self::labels_only
+    pub fn min_for_labels(self, min_for_labels: usize) -> Self {
+        Self { min_for_labels, ..self }
+    }
+
+    /// Keys to switch between [the selection keys] and labels
+    ///
+    /// By default, `<Tab>` will label the matches while you're
+    /// moving between them with the selection keys, and `<S-Tab>`
+    /// will go back to moving between them. The matches are not
+    /// searched again when switching.
+    ///
+    /// [the selection keys]: Self::select_keys
+    pub fn switch_keys(self, to_labels: KeyEvent, to_move: KeyEvent) -> Self {
+        Self {
+            to_labels_key: to_labels,
+            to_move_key: to_move,
+            ..self
+        }
+    }
+
+    /// Key to type more `char`s into the pattern
+    ///
+    /// By default, pressing `<Backspace>` while moving between the
+    /// matches will let you keep typing the pattern, past its
+    /// [length], in order to narrow down the matches. Press
+    /// `<Enter>` once you're done, and labels will be shown if there
+    /// are still [enough matches].
+    ///
+    /// While refining, no labels are shown, not even [eager ones],
+    /// since any `char` could be a part of the pattern. Wether to
+    /// label the matches is only decided on `<Enter>`.
+    ///
+    /// [length]: Self::with_len
+    /// [enough matches]: Self::min_for_labels
+    /// [eager ones]: Self::eager_labels
+    pub fn refine_key(self, refine_key: KeyEvent) -> Self {
+        Self { refine_key, ..self }
+    }
+
+    /// Always use labels, never [the selection keys]
+    ///
+    /// Once the pattern is typed, every match will be labeled,
+    /// regardless of [`min_for_labels`] or the [`Scope`], unless
+    /// there is only one match, which is selected right away. Since
+    /// the selection keys are never used, their `char`s can be used
+    /// as labels.
+    ///
+    /// [the selection keys]: Self::select_keys
+    /// [`min_for_labels`]: Self::min_for_labels
+    pub fn labels_only(self) -> Self {
+        let labels = LabelOpts { is_only: true, ..self.labels };
+        Self { labels, min_for_labels: 1, ..self }
+    }
+
+    /// Only label the matches on one side of the cursor
+    ///
+    /// Labels will be assigned to the matches after the cursor, which
+    /// keeps them short and predictable for forward motion. Pressing
+    /// `<Tab>` while the labels are shown will move them to the
+    /// matches on the other side of the cursor.
+    pub fn directional_labels(self) -> Self {
+        Self { directional_labels: true, ..self }
+    }
+
+    /// Wether the key that leaves [`Sneak`] should also be typed
+    ///
+    /// Normally, while moving between matches, any other key selects
+    /// the current match and is then discarded. With this option, that
+    /// key is sent again to the [default mode], so `s`, `xy`, then `d`
+    /// will select the match and then delete it. `<Enter>` is never
+    /// sent again, since it is used to confirm the match.
+    ///
+    /// This also applies to `char`s typed with `<Ctrl>` or `<Alt>`
+    /// while typing the pattern, which leave [`Sneak`] without moving
+    /// the selections.
+    ///
+    /// [default mode]: mode::reset
+    pub fn passthrough_exit(self, passthrough_exit: bool) -> Self {
+        Self { passthrough_exit, ..self }
+    }
+
+    /// Wether the arrow keys should move between matches
+    ///
+    /// By default, `<Down>` and `<Right>` select the next match, while
+    /// `<Up>` and `<Left>` select the previous one. If this is
+    /// `false`, they will select the current match and leave
+    /// [`Sneak`], like any other key.
+    pub fn arrow_keys(self, arrow_keys: bool) -> Self {
+        Self { arrow_keys, ..self }
+    }
+
+    /// Wether to show the selection keys when there are matches to
+    /// move between
+    ///
+    /// By default, this is `true`, and a message like "3 matches:
+    /// n next, N previous, <Enter> selects" is shown, using the
+    /// [selection keys] that were actually set.
+    ///
+    /// [selection keys]: Self::select_keys
+    pub fn hint_keys(self, shows_key_hints: bool) -> Self {
+        Self { shows_key_hints, ..self }
+    }
+
+    /// Wether moving past the last match goes back to the first one
+    ///
+    /// By default, moving between matches wraps around, and a
+    /// notification is shown when that happens. If this is `false`,
+    /// moving stops at the first and last matches instead.
+    pub fn wrap(self, wraps: bool) -> Self {
+        Self { wraps, ..self }
+    }
+
+    /// Wether a single match should be selected immediately
+    ///
+    /// By default, if there is only one match, it is selected and
+    /// [`Sneak`] is left right away. If this is `false`, the match
+    /// will be highlighted, and must be confirmed like any other.
+    pub fn auto_jump(self, auto_jump: bool) -> Self {
+        Self { auto_jump, ..self }
+    }
+
+    /// Wether to stay in [`Sneak`] when no matches are found
+    ///
+    /// By default, [`Sneak`] is left when the pattern matches
+    /// nothing. If this is `true`, you can instead fix the pattern
+    /// with `<Backspace>`, and only `<Esc>` will leave.
+    pub fn stay_on_no_match(self, stay_on_no_match: bool) -> Self {
+        Self { stay_on_no_match, ..self }
+    }
+
+    /// Extends the main selection to the match, instead of moving it
+    ///
+    /// The anchor is kept where it was, and the caret is moved to the
+    /// far end of the match. Without this option, the same can be
+    /// done by typing a label in uppercase.
+    pub fn extend(self) -> Self {
+        Self { extends: true, ..self }
+    }
+
+    /// Returns to where the main selection was after the next edit
+    ///
+    /// This lets you jump to a match, edit something there, and then
+    /// continue where you left off. The position is adjusted for the
+    /// edit, and only edits in the same [`Buffer`] count. If the edit
+    /// is done in another [`Mode`], like when inserting text, the
+    /// return only happens once that [`Mode`] is left. If you want
+    /// to return before editing, use [`jump_back`].
+    pub fn remote(self) -> Self {
+        Self { is_remote: true, ..self }
+    }
+
+    /// Selects everything between two matches
+    ///
+    /// After a match is selected, [`Sneak`] starts again, and the
+    /// second match completes the selection. If `is_inclusive`, both
+    /// matches are a part of it, otherwise only the text in between
+    /// them is selected.
+    ///
+    /// ```rust
+    /// setup_duat!(setup);
+    /// use duat::prelude::*;
+    /// use duat_sneak::Sneak;
+    ///
+    /// fn setup() {
+    ///     map::<User>("v", Sneak::new().between(true));
+    /// }
+    /// ```
+    pub fn between(self, is_inclusive: bool) -> Self {
+        Self { between: Some(is_inclusive), ..self }
+    }
+
+    /// Where the selection should be placed on a match
+    ///
+    /// By default, this is [`Target::SelectMatch`], which selects
+    /// the whole match.
+    pub fn target(self, target: Target) -> Self {
+        Self { target, ..self }
+    }
+
+    /// Wether every selection should move to the match after it
+    ///
+    /// Once the pattern is typed, each selection will move to the
+    /// first match after its caret, without going through the
+    /// matches or labels. Selections without a match after them are
+    /// left in place, unless [`drop_unmatched`] is set.
+    ///
+    /// [`drop_unmatched`]: Self::drop_unmatched
+    pub fn all_selections(self, all_selections: bool) -> Self {
+        Self { all_selections, ..self }
+    }
+
+    /// Wether to remove the selections without a match after them
+    ///
+    /// This only matters with [`all_selections`].
+    ///
+    /// [`all_selections`]: Self::all_selections
+    pub fn drop_unmatched(self, drops_unmatched: bool) -> Self {
+        Self { drops_unmatched, ..self }
+    }
+
+    /// Wether to repeat the last pattern from any [`Buffer`]
+    ///
+    /// By default, pressing a key other than a `char` will repeat
+    /// the last pattern searched in the current [`Buffer`], or the
+    /// last one searched anywhere if there is none. If this is
+    /// `true`, the last pattern searched anywhere is always used,
+    /// which is also kept when Duat is closed.
+    pub fn global_last(self, global_last: bool) -> Self {
+        Self { global_last, ..self }
+    }
+
+    /// Selects the current match if no key is pressed for a while
+    ///
+    /// While moving between matches, if `timeout` passes without any
+    /// key being pressed, the current match will be selected, as if
+    /// `<Enter>` had been pressed.
+    ///
+    /// ```rust
+    /// # use duat_sneak::Sneak;
+    /// use std::time::Duration;
+    ///
+    /// let sneak = Sneak::new().confirm_after(Duration::from_millis(750));
+    /// ```
+    pub fn confirm_after(self, timeout: Duration) -> Self {
+        Self { confirm_after: Some(timeout), ..self }
+    }
+}
+
+/// Where [`Sneak`] should look for matches
+///
+/// This is set through [`Sneak::scope`], and composes with all other
+/// options, like [`Sneak::reversed`] and [`Sneak::vertical_scope`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// Look for matches on the visible area of the screen
+    #[default]
+    Screen,
+    /// Look for matches on the whole [`Buffer`]
+    ///
+    /// Unlike other scopes, this one includes matches that are not
+    /// on screen.
+    Buffer,
+    /// Look for matches on the line of the main cursor
+    ///
+    /// Since there are usually few matches on a single line, labels
+    /// and the cloak are not shown in this scope.
+    Line,
+    /// Look for matches in the paragraph of the main cursor
+    ///
+    /// A paragraph is delimited by blank lines (including lines with
+    /// only whitespace). If the paragraph is larger than the visible
+    /// area, this will behave like [`Scope::Screen`].
+    Paragraph,
+    /// Look for matches inside of the current selections
+    ///
+    /// Matches that are partially outside of a selection are not
+    /// considered. If no selection has an anchor, this will behave
+    /// like [`Scope::Screen`].
+    Selection,
+    /// Look for matches inside of the syntax node around the cursor
+    ///
+    /// This is the smallest tree-sitter node that encloses the main
+    /// cursor and spans multiple lines, like a function or a block.
+    /// This requires the `treesitter` feature, and will behave like
+    /// [`Scope::Screen`] if it is disabled or if there is no parser
+    /// for the [`Buffer`]'s filetype.
+    Node,
+}
+
+/// Where labels are placed on their matches
+///
+/// This is set through [`Sneak::label_position`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LabelPosition {
+    /// Place labels over the first characters of the matches
+    #[default]
+    Over,
+    /// Place labels right after the matches
+    ///
+    /// This keeps the whole match visible, at the cost of shifting
+    /// the text after it.
+    After,
+}
+
+/// Where the selection is placed on a match
+///
+/// This is set through [`Sneak::target`]. When [extending], the
+/// anchor is kept, and only the caret is placed.
+///
+/// [extending]: Sneak::extend
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Target {
+    /// Select the whole match
+    ///
+    /// When extending, the caret is placed on the far end of the
+    /// match.
+    #[default]
+    SelectMatch,
+    /// Like [`Target::SelectMatch`], but with the anchor and caret
+    /// swapped
+    ///
+    /// When extending, the anchor is placed on the match, and the
+    /// caret on the far end of the selection.
+    SelectMatchSwapped,
+    /// Place the caret on the first character of the match
+    Start,
+    /// Place the caret on the last character of the match
+    End,
+    /// Place the caret on the character before the match
+    Before,
+    /// Place the caret on the character after the match
+    After,
+}
+
+impl Target {
+    /// Places a [`Cursor`] on the target of a range
+    ///
+    /// [`Cursor`]: mode::Cursor
+    fn place(self, c: &mut mode::Cursor<dyn Widget>, range: Range<usize>, is_extending: bool) {
+        if is_extending {
+            c.set_anchor_if_needed();
+        } else if !self.selects_match() {
+            c.unset_anchor();
+        }
+
+        match self {
+            Target::SelectMatch | Target::SelectMatchSwapped if is_extending => {
+                if range.start > c.caret().byte() {
+                    c.move_to(range.end);
+                    c.move_hor(-1);
+                } else {
+                    c.move_to(range.start);
+                }
+            }
+            Target::SelectMatch | Target::SelectMatchSwapped => {
+                c.move_to(range);
+            }
+            Target::Start => {
+                c.move_to(range.start);
+            }
+            Target::End => {
+                c.move_to(range.end);
+                c.move_hor(-1);
+            }
+            Target::Before => {
+                c.move_to(range.start);
+                // There is no character before the start of the text.
+                if range.start > 0 {
+                    c.move_hor(-1);
+                }
+            }
+            Target::After => {
+                c.move_to(range.end);
+            }
+        }
+
+        if self == Target::SelectMatchSwapped {
+            c.swap_ends();
+        }
+    }
+
+    /// Wether this [`Target`] selects the whole match
+    fn selects_match(self) -> bool {
+        matches!(self, Target::SelectMatch | Target::SelectMatchSwapped)
+    }
+}
+
+/// A [`Mode`] that repeats the last [`Sneak`] pattern
+///
+/// Instead of highlighting anything, this immediately selects the
+/// next (or previous) match of the last pattern in the [`Buffer`],
+/// and returns to the [default mode], much like `;` and `,` in
+/// [`vim-sneak`]. The [`Sneak`] plugin must be plugged for this to
+/// work.
+///
+/// ```rust
+/// setup_duat!(setup);
+/// use duat::prelude::*;
+/// use duat_sneak::{Repeat, SneakRepeat};
+///
+/// fn setup() {
+///     map::<User>(";", SneakRepeat::new(Repeat::Same));
+///     map::<User>(",", SneakRepeat::new(Repeat::Inverted));
+/// }
+/// ```
+///
+/// Every jump made through [`Sneak`] stores its pattern and
+/// direction, so this is also how a jump is repeated from the new
+/// position of the caret. Duat has no general mechanism to repeat
+/// the last action, so an editing mode that has one should map its
+/// repeat of [`Sneak`] jumps to [`SneakRepeat::new(Repeat::Same)`].
+///
+/// [default mode]: mode::reset
+/// [`vim-sneak`]: https://github.com/justinmk/vim-sneak
+/// [`SneakRepeat::new(Repeat::Same)`]: SneakRepeat::new
+#[derive(Clone, Copy)]
+pub struct SneakRepeat {
+    direction: Direction,
+    is_global: bool,
+}
+
+impl SneakRepeat {
+    /// Repeats the last pattern, relative to its direction
+    pub fn new(repeat: Repeat) -> Self {
+        Self { direction: Direction::Relative(repeat), is_global: false }
+    }
+
+    /// Repeats the last pattern, selecting the next match
+    pub fn forward() -> Self {
+        Self { direction: Direction::Forward, is_global: false }
+    }
+
+    /// Repeats the last pattern, selecting the previous match
+    pub fn backward() -> Self {
+        Self { direction: Direction::Backward, is_global: false }
+    }
+
+    /// Wether to repeat the last pattern from any [`Buffer`]
+    ///
+    /// By default, the last pattern searched in the current
+    /// [`Buffer`] is repeated, or the last one searched anywhere if
+    /// there is none.
+    pub fn global_last(self, is_global: bool) -> Self {
+        Self { is_global, ..self }
+    }
+}
+
+/// Which way [`SneakRepeat`] goes, relative to the last search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    /// Go in the same direction as the last search
+    Same,
+    /// Go in the opposite direction of the last search
+    Inverted,
+}
+
+/// Which way [`SneakRepeat`] goes
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Backward,
+    Relative(Repeat),
+}
+
+/// The last pattern searched with [`Sneak`], and its length
+///
+/// This is the pattern from any [`Buffer`], which is the one used
+/// with [`Sneak::global_last`]. The length is the amount of `char`s
+/// that the matches had, which might be more than the pattern's.
+pub fn last_pattern() -> Option<(String, usize)> {
+    let history = HISTORY.lock().unwrap();
+    history.first().map(|search| (search.pat.clone(), search.pattern.len))
+}
+
+/// Sets the last pattern searched with [`Sneak`]
+///
+/// It will be used as if it had been typed in full, and is added to
+/// the history of patterns. The last searches of each [`Buffer`]
+/// are forgotten, so every repeat uses this pattern.
+pub fn set_last_pattern(pat: &str) {
+    if !pat.is_empty() {
+        LAST.lock().unwrap().clear();
+        push_history(Search {
+            pat: pat.to_string(),
+            pattern: PatternOpts { len: char_len(pat), ..Sneak::new().pattern },
+            is_reversed: false,
+            target: Target::SelectMatch,
+        });
+    }
+}
+
+/// The range of the last match that was jumped to, and its
+/// [`Handle`]
+///
+/// This is the whole match, regardless of the [`Target`]. Once its
+/// [`Handle`] is edited, the range is no longer valid, so [`None`]
+/// is returned.
+pub fn last_target(pa: &Pass) -> Option<(Handle<dyn Widget>, Range<usize>)> {
+    let mut last_target = LAST_TARGET.lock().unwrap();
+    let (handle, range, version) = last_target.as_ref()?;
+
+    if handle.text(pa).version().strs == *version {
+        Some((handle.clone(), range.clone()))
+    } else {
+        *last_target = None;
+        None
+    }
+}
+
+/// Goes back to where the main selection was before the last jump
+///
+/// The position before going back is stored, so calling this again
+/// will return to the jump's destination. You can map it like any
+/// other function:
+///
+/// ```rust
+/// setup_duat!(setup);
+/// use duat::prelude::*;
+///
+/// fn setup() {
+///     map::<User>("b", duat_sneak::jump_back);
+/// }
+/// ```
+pub fn jump_back(pa: &mut Pass) {
+    let Some((handle, main)) = ORIGIN.lock().unwrap().take() else {
+        context::error!("There is no [a]Sneak[] jump to go back from");
+        return;
+    };
+
+    store_origin(pa);
+    restore_main(pa, &handle, &main);
+
+    if !context::current_widget(pa).ptr_eq(handle.widget()) {
+        mode::reset_to(pa, &handle);
+    }
+}
+
+impl Mode for SneakRepeat {
+    fn send_key(&mut self, pa: &mut Pass, _: mode::KeyEvent) {
+        mode::reset::<Buffer>(pa);
+    }
+}
+
+impl Plugin for Sneak {
+    fn plug(self, _: &mut Opts, _: &Plugins) {
+        use Step::*;
+
+        PLUGGED.store(true, Ordering::Relaxed);
+
+        // The history is kept between sessions, if the cache is available.
+        let cached = context::cache::load::<Vec<(String, usize, bool, bool)>>(HISTORY_CACHE);
+        if let Ok(history) = cached {
+            let mut current = HISTORY.lock().unwrap();
+            if current.is_empty() {
+                *current = history
+                    .into_iter()
+                    .map(|(pat, len, is_reversed, is_regex)| Search {
+                        pat,
+                        pattern: PatternOpts { len, is_regex, ..Sneak::new().pattern },
+                        is_reversed,
+                        target: Target::SelectMatch,
+                    })
+                    .collect();
+            }
+        }
+
+        hook::add::<ConfigUnloaded>(|_, _| {
+            let history: Vec<_> = HISTORY
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|search| {
+                    let Search { pat, pattern, is_reversed, .. } = search;
+                    (pat.clone(), pattern.len, *is_reversed, pattern.is_regex)
+                })
+                .collect();
+            let _ = context::cache::store(HISTORY_CACHE, history);
+        });
+
+        // Closed Buffers shouldn't be kept alive.
+        hook::add::<BufferClosed>(|_, buffer| {
+            LAST.lock().unwrap().retain(|(handle, _)| handle != buffer);
+
+            let mut last_target = LAST_TARGET.lock().unwrap();
+            if last_target.as_ref().is_some_and(|(handle, ..)| handle == buffer) {
+                *last_target = None;
+            }
+            let mut origin = ORIGIN.lock().unwrap();
+            if origin.as_ref().is_some_and(|(handle, _)| handle == buffer) {
+                *origin = None;
+            }
+            let mut remote = REMOTE.lock().unwrap();
+            if remote.as_ref().is_some_and(|remote| remote.handle == *buffer) {
+                *remote = None;
+            }
+        });
+
+        hook::add::<ModeSwitched>(move |pa, mut switch| {
+            // Edits made in other modes, like inserting, only return
+            // once the mode that Sneak returned to is back.
+            let mut remote = REMOTE.lock().unwrap();
+            if let Some(rem) = remote.as_mut() {
+                rem.is_in_mode = switch.new.name == rem.mode;
+            }
+            let returned = remote.take_if(|rem| rem.is_in_mode && rem.was_edited);
+            drop(remote);
+            if let Some(rem) = returned {
+                return_from_remote(pa, rem);
+            }
+
+            if let Some(sneak) = switch.new.get_as::<Sneak>() {
+                sneak.init(pa);
+                sneak.cloak_if_needed(pa);
+            } else if let Some(repeat) = switch.new.get_as::<SneakRepeat>() {
+                repeat_last(pa, *repeat);
+            } else if let Some(sneak) = switch.old.get_as::<Sneak>() {
+                // Only finished searches are stored, with the options
+                // they had, so a repeat performs the same search.
+                if let MatchedMove(searched, ..) | MatchedLabels(searched, ..) = &sneak.step {
+                    let search = Search { target: sneak.target, ..searched.clone() };
+                    push_history(search.clone());
+
+                    if let Some(handle) = sneak.handles.first() {
+                        let mut last = LAST.lock().unwrap();
+                        last.retain(|(other, _)| other != handle);
+                        last.push((handle.clone(), search));
+                    }
+                }
+
+                TARGETS.lock().unwrap().0.clear();
+
+                let has_jumped = sneak.handles.first().zip(sneak.origin.as_ref()).is_some_and(
+                    |(handle, origin)| {
+                        let main = handle.text(pa).main_sel().clone();
+                        main.caret() != origin.caret() || main.anchor() != origin.anchor()
+                    },
+                );
+
+                // Edits are only tracked from here on, so the ones
+                // that came before don't trigger the return.
+                if sneak.is_remote
+                    && has_jumped
+                    && let (Some(handle), Some(origin)) = (sneak.handles.first(), &sneak.origin)
+                    && let Some(buffer) = handle.try_downcast::<Buffer>()
+                {
+                    *REMOTE.lock().unwrap() = Some(Remote {
+                        handle: handle.clone(),
+                        anchor: origin.anchor().map(|anchor| anchor.byte()),
+                        caret: origin.caret().byte(),
+                        mode: switch.new.name,
+                        is_in_mode: true,
+                        was_edited: false,
+                    });
+                    let _ = buffer.read(pa).moment_for(*REMOTE_NS);
+                }
+
+                for handle in sneak.handles.iter() {
+                    let mut text = handle.text_mut(pa);
+                    text.remove_tags(*NS, ..);
+                    text.remove_tags(*CUR_NS, ..);
+                    text.remove_tags(*SEL_NS, ..);
+                }
+
+                for (handle, range) in sneak.cloaked.iter() {
+                    let mut text = handle.text_mut(pa);
+                    let end = range.end.min(text.len());
+                    text.remove_tags(*CLOAK_NS, range.start.min(end)..end);
+                }
+
+                if let Some(is_inclusive) = sneak.between
+                    && has_jumped
+                    && let Some((handle, range, _)) = LAST_TARGET.lock().unwrap().clone()
+                    && sneak.handles.first() == Some(&handle)
+                {
+                    match sneak.first.clone() {
+                        // The second target is searched for right away.
+                        None => {
+                            let second = Sneak {
+                                step: Start,
+                                handles: Vec::new(),
+                                origin: None,
+                                eager: Vec::new(),
+                                recalled: None,
+                                collected: Vec::new(),
+                                has_added: false,
+                                cloaked: Vec::new(),
+                                first: Some(range),
+                                ..sneak.clone()
+                            };
+                            context::queue(move |pa| mode::set(pa, second));
+                        }
+                        Some(first) => {
+                            let (start, end) = if is_inclusive {
+                                (first.start.min(range.start), first.end.max(range.end))
+                            } else if first.start <= range.start {
+                                (first.end, range.start)
+                            } else {
+                                (range.end, first.start)
+                            };
+
+                            handle.edit_main(pa, |mut c| {
+                                c.unset_anchor();
+                                if start < end {
+                                    c.move_to(start..end);
+                                } else {
+                                    c.move_to(start);
+                                }
+                            });
+                        }
+                    }
+                }
+            }
+        });
+
+        hook::add::<BufferUpdated>(|pa, buffer| {
+            let mut remote = REMOTE.lock().unwrap();
+            let Some(rem) = remote.as_mut().filter(|rem| rem.handle == *buffer) else {
+                return;
+            };
+
+            let moment = buffer.read(pa).moment_for(*REMOTE_NS);
+            if moment.is_empty() {
+                return;
+            }
+
+            // Positions after an edit are moved along with it.
+            let shift = |byte: usize| {
+                moment.iter().fold(byte, |byte, change| {
+                    if byte >= change.taken_end().byte() {
+                        byte.saturating_add_signed(change.shift()[0] as isize)
+                    } else {
+                        byte.min(change.start().byte())
+                    }
+                })
+            };
+            rem.anchor = rem.anchor.map(shift);
+            rem.caret = shift(rem.caret);
+            rem.was_edited = true;
+
+            if rem.is_in_mode {
+                let rem = remote.take().unwrap();
+                drop(remote);
+                return_from_remote(pa, rem);
+            }
+        });
+
+        hook::add::<OnMouseEvent>(|pa, event| {
+            if !mode::is_currently::<Sneak>()
+                || event.kind != MouseEventKind::Down(MouseButton::Left)
+            {
+                return;
+            }
+
+            // Clicking on (or right next to) a match selects it, while
+            // clicking anywhere else cancels.
+            let (target, is_extending) = {
+                let targets = TARGETS.lock().unwrap();
+                (targets.1, targets.2)
+            };
+            let clicked = event.points.and_then(|place| {
+                let point = place.points().real;
+                let targets = TARGETS.lock().unwrap();
+                let text = event.handle.text(pa);
+
+                targets
+                    .0
+                    .iter()
+                    .filter(|m| m.handle == *event.handle)
+                    .filter(|m| text.point_at_byte(m.range.start).line() == point.line())
+                    .min_by_key(|m| {
+                        if m.range.contains(&point.byte()) {
+                            0
+                        } else {
+                            m.range.start.abs_diff(point.byte())
+                        }
+                    })
+                    .filter(|m| m.range.start.abs_diff(point.byte()) <= m.range.len())
+                    .cloned()
+            });
+
+            // The click would move the main selection, so it's
+            // restored after the Buffer reacts to it, before jumping
+            // from it.
+            let main = event.handle.text(pa).main_sel().clone();
+            let handle = event.handle.clone();
+
+            context::queue(move |pa| {
+                restore_main(pa, &handle, &main);
+                match clicked {
+                    Some(m) => jump(pa, &m, is_extending, target),
+                    None => mode::reset::<Buffer>(pa),
+                }
+            });
+        });
+
+        let mut forward = self.clone();
+        forward.search.is_reversed = false;
+        let backward = self.reversed();
+
+        let base = forward.clone();
+        cmd::add("sneak", move |pa: &mut Pass, flags: cmd::Flags, pat: cmd::Remainder| {
+            if pat.0.is_empty() {
+                return Err(txt!("Can't sneak to an empty pattern"));
+            }
+
+            let sneak = base.clone().with_pattern(pat.0);
+            if flags.has_word("buffer") {
+                mode::set(pa, sneak.scope(Scope::Buffer));
+            } else {
+                mode::set(pa, sneak);
+            }
+
+            Ok(None)
+        })
+        .doc(
+            txt!("Enter [mode]Sneak[] mode with a pattern"),
+            Some(txt!("With [param.flag]--buffer[], looks in the whole buffer")),
+        );
+
+        mode::map::<mode::User>("s", move |pa: &mut Pass| mode::set(pa, forward.clone()))
+            .doc(txt!("Enter [mode]Sneak[] mode"));
+        mode::map::<mode::User>("S", move |pa: &mut Pass| mode::set(pa, backward.clone()))
+            .doc(txt!("Enter reversed [mode]Sneak[] mode"));
+        mode::map::<mode::User>(";", SneakRepeat::new(Repeat::Same))
+            .doc(txt!("Repeat the last [mode]Sneak[] pattern"));
+        mode::map::<mode::User>(",", SneakRepeat::new(Repeat::Inverted))
+            .doc(txt!("Repeat the last [mode]Sneak[] pattern in reverse"));
+
+        set_forms();
+    }
+}
+
+impl Mode for Sneak {
+    fn bindings() -> mode::Bindings {
+        mode::bindings!(match _ {
+            unmod!(Char(..)) => txt!("Filter by [key.char]{{char}}"),
+            unmod!(Tab) => txt!("Move labels to the other side of the cursor"),
+            unmod!(Enter) => txt!("Finish typing or confirm the current match"),
+            unmod!(Backspace) => txt!("Undo the last typed character"),
+        })
+    }
+
+    fn send_key(&mut self, pa: &mut Pass, key: mode::KeyEvent) {
+        set_forms();
+        let sent = KEYS_SENT.fetch_add(1, Ordering::Relaxed) + 1;
+        self.process_key(pa, key);
+        self.cloak_if_needed(pa);
+
+        // Confirms the current match, unless another key is sent first.
+        *DEADLINE.lock().unwrap() = if let Some(timeout) = self.confirm_after
+            && let Step::MatchedMove(..) = self.step
+        {
+            spawn_confirm_timer();
+            Some((Instant::now() + timeout, sent))
+        } else {
+            None
+        };
+
+        let matches = match &self.step {
+            Step::MatchedMove(_, matches, ..) => matches.clone(),
+            Step::MatchedLabels(_, matches, side, ..) => {
+                matches.iter().filter(|m| side.includes(m)).cloned().collect()
+            }
+            Step::Start | Step::Filter(..) => Vec::new(),
+        };
+        *TARGETS.lock().unwrap() = (matches, self.target, self.extends);
+    }
+}
+
+impl Sneak {
+    /// Sets up the [`Handle`]s to search in, if not done yet
+    ///
+    /// This is done when entering [`Sneak`] if it was plugged, or
+    /// on the first key otherwise.
+    fn init(&mut self, pa: &mut Pass) {
+        if !self.handles.is_empty() {
+            return;
+        }
+
+        let widget = context::current_widget(pa).clone();
+        self.origin = Some(widget.text(pa).main_sel().clone());
+        self.handles = if self.all_windows {
+            let others = context::current_window(pa)
+                .buffers(pa)
+                .into_iter()
+                .map(|buffer| buffer.to_dyn())
+                .filter(|handle| !handle.ptr_eq(widget.widget()));
+
+            std::iter::once(widget).chain(others).collect()
+        } else {
+            vec![widget]
+        };
+
+        // A pattern given beforehand is typed right away.
+        if let Some(pat) = self.prefilled.take() {
+            self.pattern.len = self.pattern.len.max(char_len(&pat));
+            for char in pat.chars() {
+                self.process_key(pa, Char(char).into());
+            }
+            if self.pattern.is_regex {
+                self.process_key(pa, Enter.into());
+            }
+        }
+    }
+
+    /// Dims the text, once there are matches highlighted
+    ///
+    /// Only the [`ModeSwitched`] hook added when plugging removes the
+    /// dimming, so nothing is dimmed without it.
+    fn cloak_if_needed(&mut self, pa: &mut Pass) {
+        if !PLUGGED.load(Ordering::Relaxed)
+            || !self.cloaked.is_empty()
+            || self.search.scope == Scope::Line
+            || matches!(self.step, Step::Start)
+            || !mode::is_currently::<Sneak>()
+        {
+            return;
+        }
+        let Some(name) = &self.cloak else {
+            return;
+        };
+
+        // Only the visible region is dimmed, since that's where the
+        // matches are shown.
+        let tag = form::id_of_non_static(name).to_tag(self.priorities.0);
+        for handle in self.handles.iter() {
+            let range = visible_range(pa, handle);
+            handle.text_mut(pa).insert_tag(*CLOAK_NS, range.clone(), tag);
+            self.cloaked.push((handle.clone(), range));
+        }
+    }
+
+    /// The next and previous selection keys, if they should be shown
+    fn keys_hint(&self) -> Option<(String, String)> {
+        self.shows_key_hints.then(|| {
+            let next = mode::keys_to_string(&[self.next_key]);
+            let prev = mode::keys_to_string(&[self.prev_key()]);
+            (next, prev)
+        })
+    }
+
+    /// Advances the [`Step`] with a key
+    fn process_key(&mut self, pa: &mut Pass, key: mode::KeyEvent) {
+        self.init(pa);
+        let labels = self.label_opts();
+        let keys_hint = self.keys_hint();
+        let handles = &self.handles;
+
+        let key = match key {
+            unmod!(Char(char)) => match self.translations.get(&char) {
+                Some(char) => Char(*char).into(),
+                None => key,
+            },
+            _ => key,
+        };
+
+        // Cancelling puts the main selection back where it was.
+        if key == Esc.into() || key == KeyEvent::new(Char('c'), KeyMod::CONTROL) {
+            if let Some(origin) = &self.origin {
+                restore_main(pa, &handles[0], origin);
+            }
+            mode::reset::<Buffer>(pa);
+            return;
+        }
+
+        // Modified chars are never a part of the pattern.
+        if let Step::Start | Step::Filter(..) = self.step
+            && is_modified_char(key)
+        {
+            mode::reset::<Buffer>(pa);
+            if self.passthrough_exit {
+                mode::type_keys([key]);
+            }
+            return;
+        }
+
+        if let Step::Start | Step::Filter(..) = self.step
+            && let unmod!(Up) | unmod!(Down) = key
+        {
+            self.recall(pa, key == Up.into());
+            return;
+        }
+
+        if key == self.select_all_key
+            && let Step::MatchedMove(_, matches, ..) | Step::MatchedLabels(_, matches, ..) =
+                &self.step
+        {
+            if matches.len() > SELECT_ALL_CAP && !self.is_confirming_all {
+                self.is_confirming_all = true;
+                context::info!("Press again to select all [a]{}[] matches", matches.len());
+            } else {
+                select_all(pa, &handles[0], matches, self.target);
+            }
+            return;
+        }
+        self.is_confirming_all = false;
+
+        if key == self.swap_key && let Step::MatchedMove(..) | Step::MatchedLabels(..) = self.step {
+            self.target = match self.target {
+                Target::SelectMatch => Target::SelectMatchSwapped,
+                Target::SelectMatchSwapped => Target::SelectMatch,
+                _ => {
+                    context::error!("Can only swap the ends when selecting the whole match");
+                    return;
+                }
+            };
+
+            let end = if self.target == Target::SelectMatch { "end" } else { "start" };
+            context::info!("The caret will be placed on the [a]{end}[] of the match");
+            return;
+        }
+
+        match &mut self.step {
+            Step::Start => {
+                // Regexes are typed incrementally.
+                if self.pattern.is_regex
+                    && let unmod!(Char(_)) = key
+                {
+                    self.step = Step::Filter(String::new(), false);
+                    self.process_key(pa, key);
+                    return;
+                }
+
+                // The last search is repeated with the options it had.
+                let (pat, pattern, finished_filtering) = if let unmod!(Char(char)) = key {
+                    (char.to_string(), self.pattern.clone(), self.pattern.len == 1)
+                } else if let Some(search) = last_search(&handles[0], self.global_last) {
+                    (search.pat, search.pattern, true)
+                } else {
+                    no_last_search_error();
+                    mode::reset::<Buffer>(pa);
+                    return;
+                };
+
+                let regex = pattern.regex(&pat);
+                if let Err(err) = regex_syntax::parse(&regex) {
+                    context::error!("[a]{pat}[] is not a valid regex: {err}");
+                    mode::reset::<Buffer>(pa);
+                    return;
+                }
+
+                let (matches, cur) =
+                    hi_matches(pa, &regex, handles, &self.search, self.priorities.1);
+
+                let Some(cur) = cur else {
+                    context::error!("No matches found for [a]{pat}");
+                    if self.stay_on_no_match {
+                        self.step = Step::Filter(pat, false);
+                    } else {
+                        mode::reset::<Buffer>(pa);
+                    }
+                    return;
+                };
+
+                let searched = Search {
+                    pat: pat.clone(),
+                    pattern,
+                    is_reversed: self.search.is_reversed,
+                    target: self.target,
+                };
+
+                self.step = if finished_filtering {
+                    if self.all_selections {
+                        jump_all(pa, &handles[0], &matches, self.target, self.drops_unmatched);
+
+                        Step::MatchedMove(searched, matches, cur, 0)
+                    } else if self.auto_jump && matches.len() == 1 {
+                        // Stop immediately if there is only one match
+                        jump(pa, &matches[0], self.extends, self.target);
+
+                        Step::MatchedMove(searched, matches, cur, 0)
+                    } else if labels.is_only
+                        || (self.search.scope != Scope::Line
+                            && matches.len() >= self.min_for_labels)
+                    {
+                        let side = LabelSide::initial(self.directional_labels, &matches);
+                        hi_labels(pa, handles, &labels, &matches, side, "", 0);
+
+                        Step::MatchedLabels(searched, matches, side, String::new(), 0)
+                    } else {
+                        hi_cur(pa, &matches[cur], &matches[cur], self.priorities.2);
+                        show_keys_hint(keys_hint.as_ref(), &matches);
+
+                        Step::MatchedMove(searched, matches, cur, 0)
+                    }
+                } else {
+                    if labels.is_eager {
+                        let pattern = &searched.pattern;
+                        self.eager = hi_eager_labels(pa, &labels, &matches, pattern, &pat);
+                    }
+                    if labels.has_hints {
+                        hi_hints(pa, &matches, char_len(&pat));
+                    }
+
+                    Step::Filter(pat, false)
+                }
+            }
+            Step::Filter(pat, is_refining) => {
+                // The current match may change, so its highlight is
+                // redone as well.
+                remove_tags(pa, handles, *NS);
+                remove_tags(pa, handles, *CUR_NS);
+
+                if let unmod!(Char(char)) = key
+                    && let Some((_, m)) = self.eager.iter().find(|(label, _)| *label == char)
+                {
+                    jump(pa, m, self.extends, self.target);
+                    return;
+                }
+                self.eager.clear();
+
+                // A recalled pattern keeps the options it was searched with.
+                let base = match &self.recalled {
+                    Some((_, pattern)) => pattern.clone(),
+                    None => self.pattern.clone(),
+                };
+
+                let finished_filtering = if let unmod!(Char(char)) = key {
+                    pat.push(char);
+                    !base.is_regex && !*is_refining && base.is_complete(pat)
+                } else if let unmod!(Enter) = key
+                    && base.is_multiline
+                    && !base.is_regex
+                    && !*is_refining
+                {
+                    pat.push('\n');
+                    base.is_complete(pat)
+                } else if let unmod!(Backspace) = key {
+                    pat.pop();
+                    if pat.is_empty() {
+                        self.recalled = None;
+                        self.step = Step::Start;
+                        return;
+                    }
+                    false
+                } else {
+                    true
+                };
+
+                // Enter finishes early, as if the pattern had the full
+                // length, but a recalled pattern keeps its own length.
+                let pattern = if let unmod!(Enter) = key
+                    && !base.is_regex
+                    && !base.is_multiline
+                {
+                    let len = match self.recalled {
+                        Some(_) => base.len.max(char_len(pat)),
+                        None => char_len(pat),
+                    };
+                    PatternOpts { len, ..base.clone() }
+                } else {
+                    // Incomplete regexes are allowed while typing.
+                    base.clone()
+                };
+                let regex = pattern.regex(pat);
+                if let Err(err) = regex_syntax::parse(&regex) {
+                    if finished_filtering {
+                        context::error!("[a]{pat}[] is not a valid regex: {err}");
+                        mode::reset::<Buffer>(pa);
+                    }
+                    return;
+                }
+
+                let (matches, cur) =
+                    hi_matches(pa, &regex, handles, &self.search, self.priorities.1);
+
+                let Some(cur) = cur else {
+                    if finished_filtering || !base.is_regex {
+                        context::error!("No matches found for [a]{pat}");
+                        if !self.stay_on_no_match {
+                            mode::reset::<Buffer>(pa);
+                        }
+                    }
+                    return;
+                };
+
+                hi_cur(pa, &matches[cur], &matches[cur], self.priorities.2);
+
+                if labels.is_eager
+                    && !finished_filtering
+                    && !base.is_regex
+                    && !*is_refining
+                {
+                    self.eager = hi_eager_labels(pa, &labels, &matches, &base, pat);
+                }
+                if labels.has_hints && !finished_filtering && !base.is_regex {
+                    hi_hints(pa, &matches, char_len(pat));
+                }
+
+                if finished_filtering {
+                    // A refined pattern can be longer than the length.
+                    let len = pattern.len.max(char_len(pat));
+                    let searched = Search {
+                        pat: pat.clone(),
+                        pattern: PatternOpts { len, ..pattern },
+                        is_reversed: self.search.is_reversed,
+                        target: self.target,
+                    };
+
+                    self.step = if self.all_selections {
+                        jump_all(pa, &handles[0], &matches, self.target, self.drops_unmatched);
+
+                        Step::MatchedMove(searched, matches, cur, 0)
+                    } else if self.auto_jump && matches.len() == 1 {
+                        // Stop immediately if there is only one match
+                        jump(pa, &matches[0], self.extends, self.target);
+
+                        Step::MatchedMove(searched, matches, cur, 0)
+                    } else if labels.is_only
+                        || (self.search.scope != Scope::Line
+                            && matches.len() >= self.min_for_labels)
+                    {
+                        let side = LabelSide::initial(self.directional_labels, &matches);
+                        hi_labels(pa, handles, &labels, &matches, side, "", 0);
+
+                        Step::MatchedLabels(searched, matches, side, String::new(), 0)
+                    } else {
+                        hi_cur(pa, &matches[cur], &matches[cur], self.priorities.2);
+                        show_keys_hint(keys_hint.as_ref(), &matches);
+
+                        Step::MatchedMove(searched, matches, cur, 0)
+                    };
+                }
+            }
+            Step::MatchedMove(searched, matches, cur, count) => {
+                let prev = *cur;
+                let len = matches.len();
+                let times = (*count).max(1);
+                let stride = times.saturating_mul(self.stride);
+
+                let with_ctrl = |key: KeyEvent| {
+                    KeyEvent::new(key.code, key.modifiers.union(KeyMod::CONTROL))
+                };
+                let is_next = key == self.next_key
+                    || key == self.repeat_key
+                    || (self.arrow_keys && matches!(key, unmod!(Down) | unmod!(Right)));
+                let is_prev = key == self.prev_key()
+                    || (self.arrow_keys && matches!(key, unmod!(Up) | unmod!(Left)));
+
+                let shift = if is_next {
+                    Some((times, true))
+                } else if is_prev {
+                    Some((times, false))
+                } else if key == with_ctrl(self.next_key) {
+                    Some((stride, true))
+                } else if key == with_ctrl(self.prev_key()) {
+                    Some((stride, false))
+                } else {
+                    None
+                };
+
+                if key == self.to_labels_key {
+                    let (searched, matches) = (searched.clone(), std::mem::take(matches));
+                    let side = LabelSide::initial(self.directional_labels, &matches);
+                    hi_labels(pa, handles, &labels, &matches, side, "", 0);
+
+                    self.step = Step::MatchedLabels(searched, matches, side, String::new(), 0);
+                } else if key == self.refine_key && !self.pattern.is_regex {
+                    remove_tags(pa, handles, *CUR_NS);
+                    self.step = Step::Filter(std::mem::take(&mut searched.pat), true);
+                } else if let Some((above, below)) = self.line_keys
+                    && (key == above || key == below)
+                {
+                    match nearest_on_other_line(pa, matches, *cur, key == below) {
+                        Some(next) => {
+                            *cur = next;
+                            *count = 0;
+                            hi_cur(pa, &matches[*cur], &matches[prev], self.priorities.2);
+                        }
+                        None => context::error!("No matches on other lines"),
+                    }
+                } else if key == self.add_key {
+                    let m = &matches[*cur];
+                    let same = |other: &Match| other.handle == m.handle && other.range == m.range;
+
+                    if let Some(i) = self.collected.iter().position(same) {
+                        self.collected.remove(i);
+                        m.handle.text_mut(pa).remove_tags(*SEL_NS, m.range.start);
+                    } else {
+                        let id = form::id_of!("sneak.selected");
+                        let tag = id.to_tag(self.priorities.2);
+                        m.handle.text_mut(pa).insert_tag(*SEL_NS, m.range.clone(), tag);
+                        self.collected.push(m.clone());
+                    }
+                } else if self.column_key == Some(key) {
+                    let m = &matches[*cur];
+                    for range in aligned_with(pa, matches, *cur) {
+                        let target = self.target;
+                        m.handle.edit_main(pa, |mut c| target.place(&mut c.copy(), range, false));
+                    }
+                    jump(pa, m, self.extends, self.target);
+                } else if let Some((delete, change, yank)) = self.operator_keys
+                    && [delete, change, yank].contains(&key)
+                {
+                    let m = &matches[*cur];
+                    if m.handle != handles[0] {
+                        context::error!("Can't operate on matches in other widgets");
+                        return;
+                    }
+
+                    let op = if key == delete {
+                        Operator::Delete
+                    } else if key == change {
+                        Operator::Change
+                    } else {
+                        Operator::Yank
+                    };
+
+                    operate(pa, m, self.target, op);
+                    if op == Operator::Change {
+                        mode::type_keys([self.insert_key]);
+                    }
+                } else if key == self.first_key || key == self.last_key {
+                    *cur = if key == self.first_key { 0 } else { len - 1 };
+                    *count = 0;
+                    hi_cur(pa, &matches[*cur], &matches[prev], self.priorities.2);
+                } else if let Some((times, is_forward)) = shift {
+                    let (next, wrapped) = shifted(*cur, len, times, is_forward, self.wraps);
+
+                    if wrapped {
+                        let end = if is_forward { "first" } else { "last" };
+                        context::info!("Wrapped around to the [a]{end}[] match");
+                    }
+
+                    *cur = next;
+                    *count = 0;
+                    hi_cur(pa, &matches[*cur], &matches[prev], self.priorities.2);
+                } else if let unmod!(Char(char)) = key
+                    && let Some(digit) = char.to_digit(10)
+                    && (*count > 0 || digit > 0)
+                {
+                    *count = count.saturating_mul(10).saturating_add(digit as usize);
+                } else {
+                    for m in self.collected.iter() {
+                        let (range, target) = (m.range.clone(), self.target);
+                        m.handle.edit_main(pa, |mut c| target.place(&mut c.copy(), range, false));
+                    }
+                    jump(pa, &matches[*cur], self.extends, self.target);
+                    if self.passthrough_exit && !matches!(key, unmod!(Enter)) {
+                        mode::type_keys([key]);
+                    }
+                }
+            }
+            Step::MatchedLabels(searched, matches, side, prefix, page) => {
+                if key == self.to_move_key {
+                    let (searched, matches) = (searched.clone(), std::mem::take(matches));
+                    let cur = if self.search.is_reversed {
+                        0
+                    } else {
+                        let ahead = matches.iter().position(|m| m.is_ahead);
+                        ahead.unwrap_or(matches.len() - 1)
+                    };
+
+                    remove_tags(pa, handles, *NS);
+                    rehi_matches(pa, &matches, self.priorities.1);
+                    hi_cur(pa, &matches[cur], &matches[cur], self.priorities.2);
+                    show_keys_hint(keys_hint.as_ref(), &matches);
+
+                    self.step = Step::MatchedMove(searched, matches, cur, 0);
+                    return;
+                }
+
+                if let unmod!(Tab) = key
+                    && *side != LabelSide::All
+                {
+                    let other = side.other();
+                    if matches.iter().any(|m| other.includes(m)) {
+                        *side = other;
+                        *page = 0;
+                        prefix.clear();
+                        hi_labels(pa, handles, &labels, matches, *side, prefix, *page);
+                    } else {
+                        context::error!("No matches on the other side of the cursor");
+                    }
+                    return;
+                }
+
+                // The matches are already labeled, so there's nothing to do.
+                if key == self.to_labels_key || matches!(key, unmod!(Tab)) {
+                    return;
+                }
+
+                if labels.is_paged {
+                    // Matches beyond the maximum are never labeled.
+                    let labeled = matches.iter().filter(|m| side.includes(m)).count();
+                    let pages = labeled.min(labels.max).div_ceil(labels.chars.len().max(1)).max(1);
+
+                    let next = matches!(key, unmod!(Char(' ')));
+                    let prev = key == KeyEvent::new(Char(' '), KeyMod::SHIFT)
+                        || (matches!(key, unmod!(Backspace)) && prefix.is_empty());
+
+                    if next || prev {
+                        *page = if next {
+                            (*page + 1) % pages
+                        } else {
+                            (*page + pages - 1) % pages
+                        };
+                        hi_labels(pa, handles, &labels, matches, *side, prefix, *page);
+                        return;
+                    }
+                }
+
+                if let unmod!(Backspace) = key {
+                    prefix.pop();
+                    hi_labels(pa, handles, &labels, matches, *side, prefix, *page);
+                    return;
+                }
+
+                // After adding selections, there's nothing left to select.
+                if let unmod!(Enter) = key
+                    && self.has_added
+                {
+                    mode::reset::<Buffer>(pa);
+                    return;
+                } else if let unmod!(Enter) = key {
+                    let labeled = assign_labels(pa, &labels, matches, *side, *page);
+                    match labeled.iter().find(|(label, _)| label.starts_with(prefix.as_str())) {
+                        Some((_, m)) => jump(pa, m, self.extends, self.target),
+                        None => mode::reset::<Buffer>(pa),
+                    }
+                    return;
+                }
+
+                let is_adding = key.modifiers == KeyMod::ALT;
+                let (Char(char), KeyMod::NONE | KeyMod::ALT) = (key.code, key.modifiers) else {
+                    context::error!("[a]{key.code:?}[] is not a valid label");
+                    mode::reset::<Buffer>(pa);
+                    return;
+                };
+
+                let is_extending = char.is_uppercase();
+                let char = char.to_lowercase().next().unwrap_or(char);
+                let typed = format!("{prefix}{char}");
+
+                let labeled = assign_labels(pa, &labels, matches, *side, *page);
+
+                if let Some((_, m)) = labeled.iter().find(|(label, _)| *label == typed) {
+                    let i = matches.iter().position(|other| std::ptr::eq(other, *m));
+                    let m = (*m).clone();
+                    if is_adding {
+                        self.has_added = true;
+                        let (range, target) = (m.range.clone(), self.target);
+                        m.handle.edit_main(pa, |mut c| target.place(&mut c.copy(), range, false));
+
+                        matches.remove(i.unwrap());
+                        prefix.clear();
+                        *page = 0;
+
+                        if matches.iter().any(|m| side.includes(m)) {
+                            hi_labels(pa, handles, &labels, matches, *side, prefix, *page);
+                        } else {
+                            mode::reset::<Buffer>(pa);
+                        }
+                    } else {
+                        jump(pa, &m, is_extending || self.extends, self.target);
+                    }
+                } else if labeled.iter().any(|(label, _)| label.starts_with(&typed)) {
+                    *prefix = typed;
+                    hi_labels(pa, handles, &labels, matches, *side, prefix, *page);
+                } else {
+                    context::error!("[a]{typed}[] is not a valid label");
+                    mode::reset::<Buffer>(pa);
+                }
+            }
+        }
+    }
+
+    /// Replaces the pattern with an older or newer one from history
+    ///
+    /// The recalled pattern is highlighted as if it had been typed,
+    /// and more `char`s can be typed before confirming with
+    /// `<Enter>`. Going past the newest pattern clears it.
+    fn recall(&mut self, pa: &mut Pass, is_older: bool) {
+        remove_tags(pa, &self.handles, *NS);
+        remove_tags(pa, &self.handles, *CUR_NS);
+        self.eager.clear();
+
+        let recalled = match (is_older, self.recalled.as_ref().map(|(i, _)| *i)) {
+            (true, recalled) => recalled.map_or(0, |i| i + 1),
+            (false, Some(i)) if i > 0 => i - 1,
+            (false, _) => {
+                self.recalled = None;
+                self.step = Step::Start;
+                return;
+            }
+        };
+
+        let Some(Search { pat, pattern, .. }) = HISTORY.lock().unwrap().get(recalled).cloned()
+        else {
+            context::error!("No older patterns in history");
+            return;
+        };
+        let regex = pattern.regex(&pat);
+        self.recalled = Some((recalled, pattern));
+
+        let (matches, cur) = hi_matches(pa, &regex, &self.handles, &self.search, self.priorities.1);
+        match cur {
+            Some(cur) => hi_cur(pa, &matches[cur], &matches[cur], self.priorities.2),
+            None => context::error!("No matches found for [a]{pat}"),
+        }
+
+        self.step = Step::Filter(pat, true);
+    }
+}
+
+/// Spawns the thread that confirms the current match once the
+/// [`DEADLINE`] is reached
+///
+/// Only one such thread is spawned, and it stops when Duat is
+/// unloading, so reloading isn't held back by it.
+fn spawn_confirm_timer() {
+    static SPAWNED: Once = Once::new();
+    SPAWNED.call_once(|| {
+        std::thread::spawn(|| {
+            while !context::will_unload() {
+                let mut deadline = DEADLINE.lock().unwrap();
+                if let Some((instant, sent)) = *deadline
+                    && instant <= Instant::now()
+                {
+                    *deadline = None;
+                    context::queue(move |_| {
+                        if KEYS_SENT.load(Ordering::Relaxed) == sent
+                            && mode::is_currently::<Sneak>()
+                        {
+                            mode::type_keys([Enter.into()]);
+                        }
+                    });
+                }
+                drop(deadline);
+
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+    });
+}
+
+/// Shows which keys move between the [`Match`]es, if there is more
+/// than one
+fn show_keys_hint(hint: Option<&(String, String)>, matches: &[Match]) {
+    if let Some((next, prev)) = hint
+        && matches.len() > 1
+    {
+        let len = matches.len();
+        context::info!(
+            "[a]{len}[] matches: [a]{next}[] next, [a]{prev}[] previous, [a]<Enter>[] selects"
+        );
+    }
+}
+
+/// The index `times` [`Match`]es away from `cur`, and wether it
+/// wrapped around
+///
+/// Without `wraps`, the index stops at the first or last [`Match`].
+fn shifted(cur: usize, len: usize, times: usize, is_forward: bool, wraps: bool) -> (usize, bool) {
+    if is_forward {
+        match cur.checked_add(times).filter(|next| *next < len) {
+            Some(next) => (next, false),
+            None if wraps => ((cur + times % len) % len, true),
+            None => (len - 1, false),
+        }
+    } else if times <= cur {
+        (cur - times, false)
+    } else if wraps {
+        ((cur + len - times % len) % len, true)
+    } else {
+        (0, false)
+    }
+}
+
+/// Highlights [`Match`]es that were already found
+fn rehi_matches(pa: &mut Pass, matches: &[Match], prio: u8) {
+    let ahead_id = form::id_of!("sneak.match.ahead");
+    let behind_id = form::id_of!("sneak.match.behind");
+
+    for m in matches {
+        let id = if m.is_ahead { ahead_id } else { behind_id };
+        m.handle.text_mut(pa).insert_tag(*NS, m.range.clone(), id.to_tag(prio));
+    }
+}
+
+/// Labels the [`Match`]es on the given side
+///
+/// Only the labels that start with the typed `prefix` are shown, and
+/// only their remaining `char`s, while the other [`Match`]es are
+/// dimmed. The [`Match`]es that aren't labeled, because they're on
+/// other pages or beyond [`max_labels`], are highlighted without a
+/// label.
+///
+/// [`max_labels`]: Sneak::max_labels
+fn hi_labels(
+    pa: &mut Pass,
+    handles: &[Handle<dyn Widget>],
+    labels: &LabelOpts,
+    matches: &[Match],
+    side: LabelSide,
+    prefix: &str,
+    page: usize,
+) {
+    remove_tags(pa, handles, *NS);
+    remove_tags(pa, handles, *CUR_NS);
+
+    let labeled = assign_labels(pa, labels, matches, side, page);
+
+    let on_side = matches.iter().filter(|m| side.includes(m)).count();
+    if labeled.len() < on_side {
+        let id = form::id_of!("sneak.match");
+        for m in matches.iter().filter(|m| side.includes(m)) {
+            if !labeled.iter().any(|(_, labeled)| labeled.range == m.range) {
+                let tag = id.to_tag(labels.priority);
+                m.handle.text_mut(pa).insert_tag(*NS, m.range.clone(), tag);
+            }
+        }
+    }
+
+    let extra = on_side.saturating_sub(labels.max);
+    if extra > 0 && prefix.is_empty() {
+        context::info!("[a]{extra}[] more matches not labeled");
+    }
+
+    let (remaining, filtered): (Vec<_>, Vec<_>) = labeled
+        .into_iter()
+        .partition(|(label, _)| label.starts_with(prefix));
+
+    let filtered_id = form::id_of!("sneak.match.filtered");
+    for (_, m) in filtered {
+        let tag = filtered_id.to_tag(labels.priority);
+        m.handle.text_mut(pa).insert_tag(*NS, m.range.clone(), tag);
+    }
+
+    // Show how many labels are left after the first one.
+    if !prefix.is_empty()
+        && let Some((_, first)) = remaining.first()
+    {
+        let count = format!("[{}]", remaining.len());
+        let count = Inlay::new(txt!("[sneak.count:239]{count}"));
+        first.handle.text_mut(pa).insert_tag(*NS, first.range.end, count);
+    }
+
+    for (label, m) in remaining {
+        let label = &label[prefix.len()..];
+        insert_label(&mut m.handle.text_mut(pa), m.range.clone(), label, labels);
+    }
+}
+
+/// Labels the [`Match`]es while the pattern is still being typed
+///
+/// Only `char`s that can't continue the pattern on any of the
+/// [`Match`]es are used, and the [`Match`]es that are too far to get
+/// a single `char` label aren't labeled. Wether a `char` continues
+/// the pattern is decided by the [`PatternOpts`], so accents,
+/// equivalences and the like are taken into account.
+fn hi_eager_labels(
+    pa: &mut Pass,
+    labels: &LabelOpts,
+    matches: &[Match],
+    pattern: &PatternOpts,
+    pat: &str,
+) -> Vec<(char, Match)> {
+    let continues = |char: char| {
+        let regex = pattern.regex(&format!("{pat}{char}"));
+        matches.iter().any(|m| {
+            let text = m.handle.text(pa);
+            let mut found = text.search(&regex).range(m.range.clone());
+            found.any(|range| range.start == m.range.start)
+        })
+    };
+
+    let eager: Vec<(char, Match)> = labels
+        .chars
+        .iter()
+        .copied()
+        .filter(|char| !continues(*char))
+        .zip(by_distance(pa, matches, LabelSide::All))
+        .map(|(label, m)| (label, m.clone()))
+        .collect();
+
+    for (label, m) in eager.iter() {
+        let (range, label) = (m.range.clone(), label.to_string());
+        insert_label(&mut m.handle.text_mut(pa), range, &label, labels);
+    }
+
+    eager
+}
+
+/// Shows the next `char` to type after each [`Match`]
+fn hi_hints(pa: &mut Pass, matches: &[Match], typed: usize) {
+    for m in matches {
+        let mut text = m.handle.text_mut(pa);
+        let Some(next) = text.get(m.range.clone()).and_then(|strs| strs.chars().nth(typed)) else {
+            continue;
+        };
+
+        if !next.is_whitespace() {
+            let hint = Inlay::new(txt!("[sneak.hint:239]{next}"));
+            text.insert_tag(*NS, m.range.end, hint);
+        }
+    }
+}
+
+/// Inserts a label on a [`Match`]'s range
+///
+/// With [`LabelPosition::After`], the label is placed after the
+/// range, but before a trailing newline, so it stays on the same
+/// line.
+///
+/// The [`Form`] of the label depends on how many lines away it is
+/// from the main caret.
+///
+/// [`Form`]: duat::form::Form
+fn insert_label(text: &mut Text, range: Range<usize>, label: &str, labels: &LabelOpts) {
+    let distance = {
+        let caret = text.main_sel().caret().line();
+        text.point_at_byte(range.start).line().abs_diff(caret)
+    };
+
+    let (near, far) = labels.distance_tiers;
+    let label_txt = |label: &str| {
+        if distance < near {
+            txt!("[sneak.label.near:239]{label}")
+        } else if distance >= far {
+            txt!("[sneak.label.far:239]{label}")
+        } else {
+            txt!("[sneak.label:239]{label}")
+        }
+    };
+
+    if labels.position == LabelPosition::After {
+        let end = if range.end > range.start && text.char_at(range.end - 1) == Some('\n') {
+            range.end - 1
+        } else {
+            range.end
+        };
+
+        text.insert_tag(*NS, end, Inlay::new(label_txt(label)));
+        return;
+    }
+
+    let b = range.start;
+
+    // Every grapheme under the label, so longer labels don't shift text.
+    let label_width = label.width();
+    let mut graphemes = Vec::new();
+    let (mut end, mut width) = (b, 0);
+    while width < label_width {
+        let grapheme = grapheme_at(text, end);
+        if grapheme.is_empty() || grapheme.ends_with('\n') {
+            break;
+        }
+
+        end += grapheme.len();
+        width += grapheme.width();
+        graphemes.push(grapheme);
+    }
+
+    // Overlaying a newline would hide the label.
+    if graphemes.is_empty() {
+        text.insert_tag(*NS, b, Inlay::new(label_txt(label)));
+    // Wide or composed graphemes are replaced, so they aren't cut.
+    } else if graphemes.iter().any(|g| g.chars().count() > 1 || g.width() > 1) {
+        let pad = " ".repeat(width.saturating_sub(label_width));
+
+        text.insert_tag(*NS, b..end, Conceal);
+        text.insert_tag(*NS, end, Inlay::new(label_txt(&format!("{label}{pad}"))));
+    } else {
+        text.insert_tag(*NS, b, Overlay::new(label_txt(label)));
+    }
+}
+
+/// Assigns a label to each [`Match`] on the given side
+///
+/// The [`Match`]es closest to the main caret get the first labels,
+/// as per [`by_distance`]. The `char`s right
+/// after the [`Match`]es are moved to the end of the alphabet, so
+/// continuing to type the pattern is unlikely to select a label by
+/// accident.
+fn assign_labels<'m>(
+    pa: &Pass,
+    labels: &LabelOpts,
+    matches: &'m [Match],
+    side: LabelSide,
+    page: usize,
+) -> Vec<(String, &'m Match)> {
+    let mut labeled = by_distance(pa, matches, side);
+    labeled.truncate(labels.max);
+
+    // Paged labels only use one char.
+    if labels.is_paged {
+        let len = labels.chars.len();
+        labeled = labeled.into_iter().skip(page * len).take(len).collect();
+    }
+
+    let unsafe_chars: Vec<char> = labeled
+        .iter()
+        .filter_map(|m| m.handle.text(pa).char_at(m.range.end))
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    let (safe, risky): (Vec<char>, Vec<char>) = labels
+        .chars
+        .iter()
+        .copied()
+        .partition(|char| !unsafe_chars.contains(char));
+    let alphabet: Vec<char> = safe.into_iter().chain(risky).collect();
+
+    iter_labels(&alphabet, labeled.len())
+        .into_iter()
+        .zip(labeled)
+        .collect()
+}
+
+/// The [`Match`]es on the given side, from nearest to farthest
+///
+/// The order of the [`Handle`]s is kept, and the distance is from
+/// each [`Handle`]'s main caret.
+fn by_distance<'m>(pa: &Pass, matches: &'m [Match], side: LabelSide) -> Vec<&'m Match> {
+    let mut labeled: Vec<_> = matches.iter().filter(|m| side.includes(m)).collect();
+
+    labeled.sort_by_cached_key(|m| {
+        let handle = matches.iter().position(|other| other.handle == m.handle);
+        let caret = m.handle.text(pa).main_sel().cursor().byte();
+        (handle, m.range.start.abs_diff(caret))
+    });
+
+    labeled
+}
+
+/// The nearest [`Match`] on a line above or below the current one
+///
+/// Of the [`Match`]es on the nearest line, the one closest to the
+/// current one's column is picked.
+fn nearest_on_other_line(
+    pa: &Pass,
+    matches: &[Match],
+    cur: usize,
+    is_below: bool,
+) -> Option<usize> {
+    let cur = &matches[cur];
+    let text = cur.handle.text(pa);
+
+    let line_and_col = |byte: usize| {
+        let line = text.point_at_byte(byte).line();
+        (line, byte - text.line(line).byte_range().start)
+    };
+    let (line, col) = line_and_col(cur.range.start);
+
+    matches
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.handle == cur.handle)
+        .map(|(i, m)| (i, line_and_col(m.range.start)))
+        .filter(|(_, (l, _))| if is_below { *l > line } else { *l < line })
+        .min_by_key(|(_, (l, c))| (l.abs_diff(line), c.abs_diff(col)))
+        .map(|(i, _)| i)
+}
+
+/// The ranges of the other [`Match`]es on the same column as `cur`
+///
+/// Columns are counted on screen, so tabs and wide characters are
+/// taken into account.
+fn aligned_with(pa: &Pass, matches: &[Match], cur: usize) -> Vec<Range<usize>> {
+    let cur = &matches[cur];
+    let popts = cur.handle.read(pa).print_opts();
+    let text = cur.handle.text(pa);
+
+    let col_of = |byte: usize| display_col(&text, byte, |col| popts.tabstop_spaces_at(col));
+    let col = col_of(cur.range.start);
+
+    let mut lines: Vec<usize> = text
+        .selections()
+        .iter()
+        .filter(|(_, is_main)| !is_main)
+        .map(|(sel, _)| sel.caret().line())
+        .collect();
+    lines.push(text.point_at_byte(cur.range.start).line());
+
+    matches
+        .iter()
+        .filter(|m| m.handle == cur.handle)
+        .filter(|m| !lines.contains(&text.point_at_byte(m.range.start).line()))
+        .filter(|m| col_of(m.range.start) == col)
+        .map(|m| m.range.clone())
+        .collect()
+}
+
+/// The column on screen of the byte `b`
+///
+/// Wide `char`s take more than one column, and `tabstop` gives how
+/// many columns a `'\t'` takes at a given column.
+fn display_col(text: &Text, b: usize, tabstop: impl Fn(u32) -> u32) -> u32 {
+    let start = text.line(text.point_at_byte(b).line()).byte_range().start;
+    text.get(start..b).map_or(0, |strs| {
+        strs.chars().fold(0, |col, char| match char {
+            '\t' => col + tabstop(col),
+            char => col + char.width().unwrap_or(0) as u32,
+        })
+    })
+}
+
+/// Sets the [`Form`]s used by [`Sneak`], if they weren't set yet
+///
+/// This is done when plugging, and also the first time [`Sneak`] is
+/// used, so it has colors even if it was only mapped.
+fn set_forms() {
+    static FORMS_SET: Once = Once::new();
+    FORMS_SET.call_once(|| {
+        form::set_weak("sneak.match", Form::mimic("default.info"));
+        form::set_weak("sneak.match.ahead", Form::mimic("sneak.match"));
+        form::set_weak("sneak.match.behind", Form::mimic("sneak.match"));
+        form::set_weak("sneak.match.filtered", Form::mimic("default.info").dim());
+        form::set_weak("sneak.label", Form::mimic("accent.info"));
+        form::set_weak("sneak.label.near", Form::mimic("sneak.label"));
+        form::set_weak("sneak.label.far", Form::mimic("sneak.label"));
+        form::set_weak("sneak.count", Form::mimic("default.info"));
+        form::set_weak("sneak.current", Form::mimic("default.info").reverse());
+        form::set_weak("sneak.selected", Form::mimic("sneak.current"));
+        form::set_weak("sneak.hint", Form::mimic("default.info").dim());
+    });
+}
+
+/// Highlights the matches on every [`Handle`], starting with the first
+///
+/// The returned `cur` is the one from the first [`Handle`] with
+/// matches.
+fn hi_matches(
+    pa: &mut Pass,
+    pat: &str,
+    handles: &[Handle<dyn Widget>],
+    search: &SearchOpts,
+    prio: u8,
+) -> (Vec<Match>, Option<usize>) {
+    let mut matches = Vec::new();
+    let mut cur = None;
+
+    for handle in handles {
+        let (ranges, handle_cur) = hi_matches_in(pa, pat, handle, search, prio);
+        cur = cur.or(handle_cur.map(|handle_cur| matches.len() + handle_cur));
+
+        let cursor = handle.text(pa).main_sel().cursor().byte();
+        matches.extend(ranges.into_iter().map(|range| Match {
+            handle: handle.clone(),
+            is_ahead: range.start > cursor,
+            range,
+        }));
+    }
+
+    (matches, cur)
+}
+
+/// The byte range of the [`Text`] shown on a [`Handle`]'s area
+fn visible_range(pa: &mut Pass, handle: &Handle<dyn Widget>) -> Range<usize> {
+    let popts = handle.read(pa).print_opts();
+    let (text, area) = pa.write_many((handle.rw_text(), handle.area()));
+
+    let start = area.start_points(&text, popts).real.byte();
+    let end = area.end_points(&text, popts).real.byte();
+    start..end
+}
+
+fn hi_matches_in(
+    pa: &mut Pass,
+    pat: &str,
+    widget: &Handle<dyn Widget>,
+    search: &SearchOpts,
+    prio: u8,
+) -> (Vec<Range<usize>>, Option<usize>) {
+    #[cfg(feature = "treesitter")]
+    let node = (search.scope == Scope::Node)
+        .then(|| node_range(pa, widget))
+        .flatten();
+    #[cfg(not(feature = "treesitter"))]
+    let node: Option<Range<usize>> = None;
+
+    let screen = visible_range(pa, widget);
     let popts = widget.read(pa).print_opts();
     let (text, area) = pa.write_many((widget.rw_text(), widget.area()));
 
-    let start = area.start_points(&text, popts).real;
-    let end = area.end_points(&text, popts).real;
-    let cursor = text.main_sel().cursor().byte();
+    let main = text.main_sel().cursor();
+    let cursor = main.byte();
+
+    let mut skip_selected = search.skip_selected;
+
+    let ranges = match search.scope {
+        Scope::Screen => vec![screen],
+        Scope::Buffer => vec![0..text.len()],
+        Scope::Line => vec![text.line(main.line()).byte_range()],
+        Scope::Paragraph => {
+            let paragraph = paragraph_range(&text, main.line());
+
+            if paragraph.len() > screen.len() {
+                vec![screen]
+            } else {
+                vec![paragraph]
+            }
+        }
+        Scope::Selection => {
+            let selections: Vec<_> = text
+                .selections()
+                .iter()
+                .filter(|(sel, _)| sel.anchor().is_some())
+                .map(|(sel, _)| sel.byte_range(&text))
+                .collect();
+
+            if selections.is_empty() {
+                vec![screen]
+            } else {
+                skip_selected = false;
+                selections
+            }
+        }
+        Scope::Node => match node {
+            Some(node) => {
+                let start = node.start.max(screen.start);
+                vec![start..node.end.min(screen.end).max(start)]
+            }
+            None => vec![screen],
+        },
+    };
+
+    let mut matches: Vec<_> = ranges
+        .into_iter()
+        .flat_map(|range| text.search(pat).range(range))
+        .filter(|range| !range.is_empty())
+        .collect();
 
-    let mut parts = text.parts();
+    if !search.include_concealed {
+        matches.retain(|range| !is_concealed(&text, range.start));
+    }
+
+    if !search.include_offscreen_columns && search.scope != Scope::Buffer {
+        matches.retain(|range| {
+            let points = TwoPoints::new_after_ghost(text.point_at_byte(range.start));
+            area.coord_at_points(&text, points, popts).is_some()
+        });
+    }
+
+    if search.at_word_start {
+        matches.retain(|range| is_word_start(&text, range.start));
+    }
+
+    if search.chars != Chars::Any {
+        matches.retain(|range| {
+            text.get(range.clone())
+                .is_some_and(|strs| search.chars.includes(strs.chars()))
+        });
+    }
+
+    if !search.excluded_captures.is_empty() {
+        matches.retain(|range| !is_in_capture(&text, range.start, &search.excluded_captures));
+    }
+
+    if skip_selected {
+        let selected: Vec<_> = text
+            .selections()
+            .iter()
+            .map(|(sel, _)| sel.byte_range(&text))
+            .collect();
+
+        matches.retain(|range| {
+            !selected
+                .iter()
+                .any(|sel| range.start < sel.end && sel.start < range.end)
+        });
+    }
+
+    // Reversed matches are ordered from nearest to farthest.
+    if search.is_reversed {
+        matches.retain(|range| range.start < cursor);
+        matches.reverse();
+    } else if search.is_forward_only {
+        matches.retain(|range| range.start > cursor);
+    }
+
+    if let Some(cols) = search.vertical_scope {
+        let col_of = |byte: usize| display_col(&text, byte, |col| popts.tabstop_spaces_at(col));
+        let (main_col, cols) = (col_of(cursor), u32::try_from(cols).unwrap_or(u32::MAX));
+        let band = main_col.saturating_sub(cols)..=main_col.saturating_add(cols);
+
+        matches.retain(|range| band.contains(&col_of(range.start)));
+    }
 
-    let matches: Vec<_> = parts.strs.search(pat).range(start..end).collect();
+    let mut parts = text.parts();
 
-    let id = form::id_of!("sneak.match");
+    let ahead_id = form::id_of!("sneak.match.ahead");
+    let behind_id = form::id_of!("sneak.match.behind");
 
     let ns = *NS;
     let mut next = None;
@@ -434,44 +3168,678 @@ fn hi_matches(pa: &mut Pass, pat: &str, widget: &Handle<dyn Widget>) -> (Vec<Ran
         if range.start > cursor && next.is_none() {
             next = Some(i);
         }
-        parts.tags.insert(ns, range.clone(), id.to_tag(239));
+
+        let id = if range.start > cursor { ahead_id } else { behind_id };
+        parts.tags.insert(ns, range.clone(), id.to_tag(prio));
+    }
+
+    if search.is_reversed {
+        let first = (!matches.is_empty()).then_some(0);
+        (matches, first)
+    } else {
+        let last = matches.len().checked_sub(1);
+        (matches, next.or(last))
     }
+}
 
-    let last = matches.len().checked_sub(1);
-    (matches, next.or(last))
+fn hi_cur(pa: &mut Pass, cur: &Match, prev: &Match, prio: u8) {
+    if prev.handle != cur.handle {
+        prev.handle.text_mut(pa).remove_tags(*CUR_NS, prev.range.start);
+    }
+    hi_cur_in(&mut cur.handle.text_mut(pa), cur.range.clone(), prio);
 }
 
-fn hi_cur(pa: &mut Pass, handle: &Handle<dyn Widget>, cur: Range<usize>, prev: Range<usize>) {
+/// Highlights the current match, removing the previous highlights
+fn hi_cur_in(text: &mut Text, range: Range<usize>, prio: u8) {
     let cur_id = form::id_of!("sneak.current");
 
-    let mut text = handle.text_mut(pa);
-    text.remove_tags(*CUR_NS, prev.start);
-    text.insert_tag(*CUR_NS, cur, cur_id.to_tag(240));
+    text.remove_tags(*CUR_NS, ..);
+    text.insert_tag(*CUR_NS, range, cur_id.to_tag(prio));
+}
+
+/// Selects or extends to a [`Target`] of a [`Match`], exiting
+/// [`Sneak`]
+///
+/// If the [`Match`] is on another [`Handle`], it will be focused.
+fn jump(pa: &mut Pass, m: &Match, is_extending: bool, target: Target) {
+    store_origin(pa);
+    let range = m.range.clone();
+    m.handle.edit_main(pa, |mut c| target.place(&mut c, range, is_extending));
+    store_target(pa, &m.handle, m.range.clone());
+
+    exit_to(pa, &m.handle);
+}
+
+/// Applies an [`Operator`] from the main caret up to a [`Match`],
+/// exiting [`Sneak`]
+fn operate(pa: &mut Pass, m: &Match, target: Target, op: Operator) {
+    store_origin(pa);
+    let range = m.range.clone();
+    m.handle.edit_main(pa, |mut c| {
+        let caret = c.caret();
+        c.unset_anchor();
+        target.place(&mut c, range, true);
+
+        if op == Operator::Yank {
+            duat::clipboard::set(c.selection());
+            c.unset_anchor();
+            c.move_to(caret);
+        } else {
+            c.replace("");
+            c.unset_anchor();
+        }
+    });
+    store_target(pa, &m.handle, m.range.clone());
+
+    exit_to(pa, &m.handle);
+}
+
+/// Moves the main selection back to where a [`Remote`] started
+fn return_from_remote(pa: &mut Pass, remote: Remote) {
+    remote.handle.edit_main(pa, |mut c| {
+        c.unset_anchor();
+        if let Some(anchor) = remote.anchor {
+            c.move_to(anchor);
+            c.set_anchor();
+        }
+        c.move_to(remote.caret);
+    });
+}
+
+/// Adds a selection on every [`Match`], exiting [`Sneak`]
+///
+/// The main selection goes to the [`Match`] nearest to it.
+fn select_all(pa: &mut Pass, handle: &Handle<dyn Widget>, matches: &[Match], target: Target) {
+    let caret = handle.text(pa).main_sel().cursor().byte();
+    let nearest = matches
+        .iter()
+        .filter(|m| m.handle == *handle)
+        .min_by_key(|m| m.range.start.abs_diff(caret))
+        .unwrap_or(&matches[0]);
+
+    for m in matches.iter().filter(|m| !std::ptr::eq(*m, nearest)) {
+        let range = m.range.clone();
+        m.handle.edit_main(pa, |mut c| target.place(&mut c.copy(), range, false));
+    }
+
+    jump(pa, nearest, false, target);
+}
+
+/// Moves every selection to the first [`Match`] after it
+///
+/// Selections without a [`Match`] after them are left in place, or
+/// removed if `drops_unmatched`.
+fn jump_all(
+    pa: &mut Pass,
+    handle: &Handle<dyn Widget>,
+    matches: &[Match],
+    target: Target,
+    drops_unmatched: bool,
+) {
+    let mut ranges: Vec<_> = matches
+        .iter()
+        .filter(|m| m.handle == *handle)
+        .map(|m| m.range.clone())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+
+    store_origin(pa);
+    handle.edit_all(pa, |mut c| {
+        let caret = c.caret().byte();
+        match ranges.iter().find(|range| range.start > caret) {
+            Some(range) => target.place(&mut c, range.clone(), false),
+            None if drops_unmatched => c.destroy(),
+            None => {}
+        }
+    });
+
+    mode::reset::<Buffer>(pa);
+}
+
+/// Selects the next match of the last pattern in the whole [`Buffer`]
+///
+/// This is what [`SneakRepeat`] does, returning to the default mode
+/// right after.
+fn repeat_last(pa: &mut Pass, repeat: SneakRepeat) {
+    let handle = context::current_widget(pa).clone();
+    let Some(search) = last_search(&handle, repeat.is_global) else {
+        no_last_search_error();
+        mode::reset::<Buffer>(pa);
+        return;
+    };
+
+    let is_reversed = match repeat.direction {
+        Direction::Forward => false,
+        Direction::Backward => true,
+        Direction::Relative(Repeat::Same) => search.is_reversed,
+        Direction::Relative(Repeat::Inverted) => !search.is_reversed,
+    };
+
+    let regex = search.pattern.regex(&search.pat);
+
+    let found = {
+        let text = handle.text(pa);
+        let caret = text.main_sel().cursor().byte();
+        let mut matches = text
+            .search(&regex)
+            .range(0..text.len())
+            .filter(|range| !range.is_empty());
+
+        // Like in vim, a "till" repeat skips the adjacent match, or it
+        // would never move.
+        if is_reversed && search.target == Target::After {
+            matches.filter(|range| range.end < caret).last()
+        } else if is_reversed {
+            matches.filter(|range| range.start < caret).last()
+        } else if search.target == Target::Before {
+            let next = caret + grapheme_at(&text, caret).len();
+            matches.find(|range| range.start > next)
+        } else {
+            matches.find(|range| range.start > caret)
+        }
+    };
+
+    match found {
+        Some(range) => {
+            store_origin(pa);
+            handle.edit_main(pa, |mut c| search.target.place(&mut c, range.clone(), false));
+            store_target(pa, &handle, range);
+        }
+        None => context::error!("No more matches for [a]{}", search.pat),
+    }
+
+    mode::reset::<Buffer>(pa);
+}
+
+/// Adds a pattern to the front of the history, removing duplicates
+fn push_history(search: Search) {
+    let mut history = HISTORY.lock().unwrap();
+    history.retain(|other| other.pat != search.pat);
+    history.insert(0, search);
+    history.truncate(HISTORY_LEN);
+}
+
+/// The last [`Search`] in a [`Handle`]
+///
+/// If `is_global`, or if there was no [`Search`] in the [`Handle`],
+/// this is the last [`Search`] anywhere, which may come from a
+/// previous session.
+fn last_search(handle: &Handle<dyn Widget>, is_global: bool) -> Option<Search> {
+    let last = (!is_global).then(|| {
+        let last = LAST.lock().unwrap();
+        last.iter().find(|(other, _)| other == handle).map(|(_, search)| search.clone())
+    });
+
+    last.flatten().or_else(|| HISTORY.lock().unwrap().first().cloned())
+}
+
+fn no_last_search_error() {
+    context::error!("mode hasn't been set to [a]Sneak[] yet");
+}
+
+/// Stores the main selection before a jump, for [`jump_back`]
+fn store_origin(pa: &Pass) {
+    let handle = context::current_widget(pa).clone();
+    let main = handle.text(pa).main_sel().clone();
+    *ORIGIN.lock().unwrap() = Some((handle, main));
+}
+
+/// Stores the range of the [`Match`] that was jumped to, for
+/// [`last_target`]
+fn store_target(pa: &Pass, handle: &Handle<dyn Widget>, range: Range<usize>) {
+    let version = handle.text(pa).version().strs;
+    *LAST_TARGET.lock().unwrap() = Some((handle.clone(), range, version));
+}
+
+/// Moves the main selection back to how it was
+fn restore_main(pa: &mut Pass, handle: &Handle<dyn Widget>, main: &mode::Selection) {
+    handle.edit_main(pa, |mut c| {
+        c.unset_anchor();
+        if let Some(anchor) = main.anchor() {
+            c.move_to(anchor);
+            c.set_anchor();
+        }
+        c.move_to(main.caret());
+    });
+}
+
+/// Exits [`Sneak`], focusing on the [`Handle`] if it isn't already
+fn exit_to(pa: &mut Pass, handle: &Handle<dyn Widget>) {
+    if context::current_widget(pa).ptr_eq(handle.widget()) {
+        mode::reset::<Buffer>(pa);
+    } else {
+        mode::reset_to(pa, handle);
+    }
+}
+
+fn remove_tags(pa: &mut Pass, handles: &[Handle<dyn Widget>], ns: Ns) {
+    for handle in handles {
+        handle.text_mut(pa).remove_tags(ns, ..);
+    }
+}
+
+/// The range of the paragraph around a line, delimited by blank lines
+fn paragraph_range(text: &Text, line: usize) -> Range<usize> {
+    let is_blank = |line: usize| text.line(line).chars().all(char::is_whitespace);
+    let last = text.last_point().line();
+
+    let start = (0..line).rev().find(|&l| is_blank(l)).map_or(0, |l| l + 1);
+    let end = (line + 1..=last).find(|&l| is_blank(l)).map_or(last, |l| l - 1);
+
+    text.line(start).byte_range().start..text.line(end).byte_range().end
+}
+
+/// The range of the smallest multiline tree-sitter node around the
+/// main cursor
+#[cfg(feature = "treesitter")]
+fn node_range(pa: &mut Pass, widget: &Handle<dyn Widget>) -> Option<Range<usize>> {
+    use duat_treesitter::TsHandle;
+
+    let handle = widget.try_downcast::<Buffer>()?;
+    let (parser, buffer) = handle.get_ts_parser(pa)?;
+
+    let cursor = buffer.text().main_sel().cursor().byte();
+    let mut node = parser.root_node().descendant_for_byte_range(cursor, cursor)?;
+
+    while node.start_position().row == node.end_position().row {
+        node = node.parent()?;
+    }
+
+    Some(node.byte_range())
+}
+
+/// The length of a pattern in `char`s
+///
+/// Typed characters may take more than one byte, so the byte length
+/// of a pattern must never be compared against [`with_len`].
+///
+/// [`with_len`]: Sneak::with_len
+fn char_len(pat: &str) -> usize {
+    pat.chars().count()
+}
+
+/// The grapheme cluster that starts on the byte `b`
+fn grapheme_at(text: &Text, b: usize) -> String {
+    // No grapheme cluster is realistically longer than this.
+    const MAX_CHARS: usize = 32;
+
+    let Some(strs) = text.get(b..) else {
+        return String::new();
+    };
+    let start: String = strs.chars().take(MAX_CHARS).collect();
+
+    start.graphemes(true).next().unwrap_or_default().to_string()
+}
+
+/// All accented variants of a `char`, including its base form
+fn accent_class(char: char) -> Option<String> {
+    const VARIANTS: &[(char, &str)] = &[
+        ('a', "àáâãäåāăą"),
+        ('c', "çćĉċč"),
+        ('d', "ďđ"),
+        ('e', "èéêëēĕėęě"),
+        ('g', "ĝğġģ"),
+        ('i', "ìíîïĩīĭį"),
+        ('l', "ĺļľł"),
+        ('n', "ñńņň"),
+        ('o', "òóôõöøōŏő"),
+        ('r', "ŕŗř"),
+        ('s', "śŝşš"),
+        ('t', "ţť"),
+        ('u', "ùúûüũūŭůűų"),
+        ('y', "ýÿŷ"),
+        ('z', "źżž"),
+    ];
+
+    let lower = char.to_lowercase().next()?;
+    let (base, variants) = VARIANTS
+        .iter()
+        .find(|(base, variants)| *base == lower || variants.contains(lower))?;
+
+    let class = std::iter::once(*base).chain(variants.chars());
+    Some(if char.is_uppercase() {
+        class.flat_map(char::to_uppercase).collect()
+    } else {
+        class.collect()
+    })
+}
+
+/// Wether a key is a `char` typed with `<C->` or `<A->`
+fn is_modified_char(key: KeyEvent) -> bool {
+    matches!(key.code, Char(_)) && key.modifiers.intersects(KeyMod::CONTROL | KeyMod::ALT)
+}
+
+/// Wether a `char` can be part of an identifier
+fn is_identifier(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+/// Wether the byte `b` is at the start of a word
+///
+/// This includes `camelCase` humps and the characters after
+/// underscores.
+fn is_word_start(text: &Text, b: usize) -> bool {
+    #[derive(PartialEq)]
+    enum Category {
+        Space,
+        Word,
+        Symbol,
+    }
+
+    let category = |char: char| {
+        if char.is_whitespace() {
+            Category::Space
+        } else if is_identifier(char) {
+            Category::Word
+        } else {
+            Category::Symbol
+        }
+    };
+
+    let Some(cur) = text.char_at(b) else {
+        return false;
+    };
+    let Some(prev) = text.get(..b).and_then(|strs| strs.chars().next_back()) else {
+        return true;
+    };
+
+    category(prev) != category(cur)
+        || (prev.is_lowercase() && cur.is_uppercase())
+        || (prev == '_' && cur != '_')
+}
+
+/// Wether the byte `b` is inside of a [`Conceal`]ed region
+///
+/// [`Conceal`]: duat::text::Conceal
+fn is_concealed(text: &Text, b: usize) -> bool {
+    let mut ends = 0;
+
+    for (_, tag) in text.raw_tags_rev(b + 1) {
+        match tag {
+            RawTag::EndConceal(_) => ends += 1,
+            RawTag::StartConceal(_) if ends == 0 => return true,
+            RawTag::StartConceal(_) => ends -= 1,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Wether the byte `b` is inside of one of the `captures`
+///
+/// The captures are the [`Form`]s applied by syntax highlighting,
+/// and a capture also includes all of its children.
+///
+/// [`Form`]: duat::form::Form
+fn is_in_capture(text: &Text, b: usize, captures: &[String]) -> bool {
+    let is_capture = |name: &str| {
+        captures.iter().any(|cap| {
+            name.strip_prefix(cap.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    };
+
+    let mut popped = Vec::new();
+
+    for (_, tag) in text.raw_tags_rev(b + 1) {
+        match tag {
+            RawTag::PopForm(_, id) => popped.push(id),
+            RawTag::PushForm(_, id, _) => {
+                if let Some(i) = popped.iter().position(|popped| *popped == id) {
+                    popped.swap_remove(i);
+                } else if is_capture(id.name()) {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// The labels for `total` matches
+///
+/// No label is the prefix of another, so every label can be typed in
+/// full. The first `char`s are used as prefixes for longer labels,
+/// while the last ones are kept as single `char` labels.
+fn iter_labels(labels: &[char], total: usize) -> Vec<String> {
+    let n = labels.len();
+
+    if total <= n || n == 1 {
+        return labels.iter().take(total).map(char::to_string).collect();
+    }
+
+    // Each prefix replaces one single char label with n labels.
+    let prefixes = (1..=n).find(|k| (n - k) + k * n >= total).unwrap_or(n);
+    let rest = total - (n - prefixes);
+
+    let longer = labels[..prefixes].iter().enumerate().flat_map(|(i, prefix)| {
+        let count = rest / prefixes + (i < rest % prefixes) as usize;
+        iter_labels(labels, count)
+            .into_iter()
+            .map(move |label| format!("{prefix}{label}"))
+    });
+
+    labels[prefixes..]
+        .iter()
+        .map(char::to_string)
+        .chain(longer)
+        .collect()
+}
+
+/// How [`Sneak`] should treat the case of the pattern
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Sensitive,
+    Insensitive,
+    Smart,
+}
+
+/// Options that change how the typed pattern is matched
+#[derive(Clone)]
+struct PatternOpts {
+    /// The length of the pattern, in `char`s, not bytes
+    len: usize,
+    case: Case,
+    is_regex: bool,
+    is_whole_word: bool,
+    equivalences: Vec<(char, String)>,
+    space_matches_whitespace: bool,
+    is_multiline: bool,
+    ignore_accents: bool,
+}
+
+impl PatternOpts {
+    /// Builds the regex that matches `pat` followed by enough
+    /// characters to reach `len`
+    ///
+    /// The characters in `pat` are matched literally, unless in regex
+    /// mode.
+    fn regex(&self, pat: &str) -> String {
+        let is_case_sensitive = match self.case {
+            Case::Sensitive => true,
+            Case::Insensitive => false,
+            Case::Smart => pat.chars().any(char::is_uppercase),
+        };
+
+        let should_ci = if is_case_sensitive { "" } else { "(?i)" };
+        let b = if self.is_whole_word { r"\b" } else { "" };
+
+        if self.is_regex {
+            return format!("{should_ci}{b}(?:{pat}){b}");
+        }
+
+        let remaining = self.len.saturating_sub(char_len(pat));
+        let pat: String = pat.chars().map(|char| self.char_regex(char)).collect();
+
+        let any = if self.is_multiline { "(?s:.)" } else { r"[^\n]" };
+
+        format!("{should_ci}{b}{pat}{any}{{{remaining}}}{b}")
+    }
+
+    /// Wether enough `char`s have been typed to stop filtering
+    fn is_complete(&self, pat: &str) -> bool {
+        char_len(pat) >= self.len
+    }
+
+    /// The regex for a single typed `char`
+    fn char_regex(&self, char: char) -> String {
+        let escape = |char: char| regex_syntax::escape(char.encode_utf8(&mut [0; 4]));
+
+        match self.equivalences.iter().find(|(key, _)| *key == char) {
+            Some((key, class)) => {
+                let class: String = std::iter::once(*key)
+                    .chain(class.chars())
+                    .map(escape)
+                    .collect();
+                format!("[{class}]")
+            }
+            None if char == ' ' && self.space_matches_whitespace => r"[\t ]".to_string(),
+            None if self.ignore_accents
+                && let Some(class) = accent_class(char) =>
+            {
+                format!(r"(?:[{class}]\p{{M}}*)")
+            }
+            None => escape(char),
+        }
+    }
+}
+
+/// Options that change how labels are assigned and shown
+#[derive(Clone)]
+struct LabelOpts {
+    chars: Vec<char>,
+    position: LabelPosition,
+    distance_tiers: (usize, usize),
+    is_eager: bool,
+    has_hints: bool,
+    is_paged: bool,
+    is_only: bool,
+    max: usize,
+    /// The priority of the highlights on the matches
+    priority: u8,
+}
+
+/// Options that restrict which matches will be found
+#[derive(Clone)]
+struct SearchOpts {
+    scope: Scope,
+    is_reversed: bool,
+    is_forward_only: bool,
+    vertical_scope: Option<usize>,
+    include_concealed: bool,
+    skip_selected: bool,
+    include_offscreen_columns: bool,
+    at_word_start: bool,
+    excluded_captures: Vec<String>,
+    chars: Chars,
+}
+
+/// Which kinds of `char`s a [`Match`] must be made of
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Chars {
+    Any,
+    Identifiers,
+    Symbols,
+}
+
+impl Chars {
+    /// Wether every `char` in `matched` is of this kind
+    fn includes(&self, mut matched: impl Iterator<Item = char>) -> bool {
+        match self {
+            Chars::Any => true,
+            Chars::Identifiers => matched.all(is_identifier),
+            Chars::Symbols => matched.all(|char| !is_identifier(char) && !char.is_whitespace()),
+        }
+    }
+}
+
+/// A finished search, which can be repeated
+#[derive(Clone)]
+struct Search {
+    pat: String,
+    /// The options that the pattern was searched with, including
+    /// its length
+    pattern: PatternOpts,
+    is_reversed: bool,
+    target: Target,
+}
+
+/// A match of the pattern on a given [`Handle`]
+#[derive(Clone)]
+struct Match {
+    handle: Handle<dyn Widget>,
+    /// The byte range of the match
+    range: Range<usize>,
+    is_ahead: bool,
 }
 
-fn iter_labels(total: usize) -> impl Iterator<Item = char> {
-    const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
+/// Where to return to after editing at a remote [`Match`]
+struct Remote {
+    handle: Handle<dyn Widget>,
+    anchor: Option<usize>,
+    caret: usize,
+    /// The [`Mode`] that [`Sneak`] returned to
+    mode: &'static str,
+    is_in_mode: bool,
+    was_edited: bool,
+}
+
+/// What to do with the text up to a [`Match`]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// Which [`Match`]es should be labeled
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LabelSide {
+    All,
+    Ahead,
+    Behind,
+}
 
-    let multiple = total / LETTERS.len();
+impl LabelSide {
+    /// The starting side, prefering the matches ahead of the cursor
+    fn initial(directional: bool, matches: &[Match]) -> Self {
+        if !directional {
+            Self::All
+        } else if matches.iter().any(|m| m.is_ahead) {
+            Self::Ahead
+        } else {
+            Self::Behind
+        }
+    }
 
-    let singular = LETTERS.chars().skip(multiple);
+    /// The opposite side of the cursor
+    fn other(self) -> Self {
+        match self {
+            Self::All => Self::All,
+            Self::Ahead => Self::Behind,
+            Self::Behind => Self::Ahead,
+        }
+    }
 
-    singular
-        .chain(
-            LETTERS
-                .chars()
-                .take(multiple)
-                .flat_map(|c| std::iter::repeat_n(c, 26)),
-        )
-        .take(total)
+    /// Wether a [`Match`] should be labeled
+    fn includes(self, m: &Match) -> bool {
+        match self {
+            Self::All => true,
+            Self::Ahead => m.is_ahead,
+            Self::Behind => !m.is_ahead,
+        }
+    }
 }
 
 #[derive(Clone)]
 enum Step {
     Start,
-    Filter(String),
-    MatchedMove(String, Vec<Range<usize>>, usize),
-    MatchedLabels(String, Vec<Range<usize>>),
+    Filter(String, bool),
+    MatchedMove(Search, Vec<Match>, usize, usize),
+    MatchedLabels(Search, Vec<Match>, LabelSide, String, usize),
 }
 
 impl Default for Sneak {
@@ -479,3 +3847,198 @@ impl Default for Sneak {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The byte ranges where `pat` is found in `text`
+    fn find(pattern: &PatternOpts, pat: &str, text: &str) -> Vec<Range<usize>> {
+        let regex = pattern.regex(pat);
+        assert!(regex_syntax::parse(&regex).is_ok(), "{regex} is not a valid regex");
+
+        let text = Text::from(text);
+        text.search(&regex).range(0..text.len()).collect()
+    }
+
+    fn with_len(len: usize) -> PatternOpts {
+        PatternOpts { len, ..Sneak::new().pattern }
+    }
+
+    #[test]
+    fn metacharacters_are_matched_literally() {
+        for pat in [".", "*", "(", "\\", "{", "$"] {
+            let text = format!("ab {pat}c");
+            assert_eq!(find(&with_len(2), pat, &text), vec![3..5], "typed {pat}");
+        }
+    }
+
+    #[test]
+    fn metacharacters_are_escaped_in_the_middle_of_patterns() {
+        assert_eq!(find(&with_len(3), "a.", "abc a.c"), vec![4..7]);
+        assert_eq!(find(&with_len(3), "{$", "{a} {$}"), vec![4..7]);
+        assert_eq!(find(&with_len(2), "\\(", "( \\("), vec![2..4]);
+    }
+
+    #[test]
+    fn whole_words_only_match_full_words() {
+        let pattern = PatternOpts { is_whole_word: true, ..with_len(2) };
+
+        assert_eq!(find(&pattern, "in", "String in"), vec![7..9]);
+        assert_eq!(find(&pattern, "in", "in String"), vec![0..2]);
+        assert_eq!(find(&pattern, "i", "String in"), vec![7..9]);
+        assert!(find(&pattern, "in", "String").is_empty());
+    }
+
+    #[test]
+    fn whole_words_respect_the_case() {
+        let pattern = PatternOpts { is_whole_word: true, case: Case::Sensitive, ..with_len(2) };
+
+        assert_eq!(find(&pattern, "in", "IN in"), vec![3..5]);
+    }
+
+    #[test]
+    fn lengths_are_counted_in_chars() {
+        assert_eq!(char_len("ção"), 3);
+        assert!(with_len(2).is_complete("日本"));
+        assert!(!with_len(3).is_complete("日本"));
+
+        assert_eq!(find(&with_len(2), "日", "日本語"), vec![0..6]);
+        assert_eq!(find(&with_len(3), "日", "日本語"), vec![0..9]);
+        assert_eq!(find(&with_len(2), "🦀", "a🦀🦀"), vec![1..9]);
+        assert_eq!(find(&with_len(3), "çã", "ação"), vec![1..6]);
+    }
+
+    #[test]
+    fn captures_include_their_children() {
+        let mut text = Text::from("ab // ab\n\"ab\"");
+        text.insert_tag(*NS, 3..8, form::id_of!("comment.line").to_tag(0));
+        text.insert_tag(*NS, 9..13, form::id_of!("string").to_tag(0));
+
+        let captures = ["comment".to_string(), "string".to_string()];
+        assert!(!is_in_capture(&text, 0, &captures));
+        assert!(is_in_capture(&text, 6, &captures));
+        assert!(is_in_capture(&text, 10, &captures));
+        assert!(!is_in_capture(&text, 10, &["comment".to_string()]));
+        assert!(!is_in_capture(&text, 6, &["comm".to_string()]));
+    }
+
+    #[test]
+    fn numeric_labels_come_first() {
+        let labels = Sneak::new().numeric_labels().label_opts();
+        let expected: Vec<String> = "123456789abcdef".chars().map(String::from).collect();
+
+        assert_eq!(iter_labels(&labels.chars, 15), expected);
+    }
+
+    #[test]
+    fn longer_labels_use_the_first_chars_as_prefixes() {
+        let labels = iter_labels(&['a', 'b', 'c'], 7);
+
+        assert_eq!(labels, ["c", "aa", "ab", "ac", "ba", "bb", "bc"]);
+    }
+
+    #[test]
+    fn no_label_is_a_prefix_of_another() {
+        let chars: Vec<char> = "asdfg".chars().collect();
+
+        for total in 1..=200 {
+            let labels = iter_labels(&chars, total);
+            assert_eq!(labels.len(), total);
+
+            for (i, label) in labels.iter().enumerate() {
+                for (j, other) in labels.iter().enumerate() {
+                    assert!(i == j || !other.starts_with(label.as_str()), "{label} in {other}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn select_keys_are_not_labels() {
+        let sneak = Sneak::new().select_keys('a', 'b').min_for_labels(2);
+        let labels = sneak.label_opts();
+
+        assert!(!labels.chars.contains(&'a'));
+        assert!(!labels.chars.contains(&'b'));
+        assert_eq!(iter_labels(&labels.chars, 1), ["c"]);
+    }
+
+    #[test]
+    fn select_keys_are_labels_when_only_labeling() {
+        let labels = Sneak::new().select_keys('a', 'b').labels_only().label_opts();
+
+        assert!(labels.chars.starts_with(&['a', 'b']));
+    }
+
+    #[test]
+    fn shifting_wraps_around_the_matches() {
+        assert_eq!(shifted(1, 5, 2, true, true), (3, false));
+        assert_eq!(shifted(3, 5, 4, true, true), (2, true));
+        assert_eq!(shifted(3, 5, 4, true, false), (4, false));
+        assert_eq!(shifted(1, 5, 3, false, true), (3, true));
+        assert_eq!(shifted(1, 5, 3, false, false), (0, false));
+    }
+
+    #[test]
+    fn huge_counts_dont_overflow() {
+        let times = usize::MAX;
+        assert_eq!(shifted(3, 5, times, true, true), ((3 + times % 5) % 5, true));
+        assert_eq!(shifted(3, 5, times, true, false), (4, false));
+        assert_eq!(shifted(3, 5, times, false, true), ((3 + 5 - times % 5) % 5, true));
+        assert_eq!(shifted(3, 5, times, false, false), (0, false));
+    }
+
+    #[test]
+    fn modified_chars_are_not_typed() {
+        assert!(is_modified_char(KeyEvent::new(Char('s'), KeyMod::CONTROL)));
+        assert!(is_modified_char(KeyEvent::new(Char('x'), KeyMod::ALT)));
+        assert!(is_modified_char(KeyEvent::new(Char('X'), KeyMod::ALT | KeyMod::SHIFT)));
+        assert!(!is_modified_char(Char('s').into()));
+        assert!(!is_modified_char(KeyEvent::new(Char('S'), KeyMod::SHIFT)));
+        assert!(!is_modified_char(KeyEvent::new(Enter, KeyMod::CONTROL)));
+    }
+
+    #[test]
+    fn only_the_latest_current_match_is_highlighted() {
+        let mut text = Text::from("abc abd abe");
+        let cur_id = form::id_of!("sneak.current");
+
+        // As if the current match moved after each typed char.
+        for range in [0..3, 4..7, 8..11] {
+            hi_cur_in(&mut text, range, 0);
+        }
+
+        let highlighted: Vec<usize> = text
+            .raw_tags_rev(text.len())
+            .filter_map(|(b, tag)| {
+                matches!(tag, RawTag::PushForm(_, id, _) if id == cur_id).then_some(b)
+            })
+            .collect();
+        assert_eq!(highlighted, [8]);
+    }
+
+    #[test]
+    fn columns_count_tabs_and_wide_chars() {
+        let text = Text::from("a\tb\n日本\tc\n");
+        let tabstop = |col: u32| 4 - col % 4;
+
+        assert_eq!(display_col(&text, 0, tabstop), 0);
+        assert_eq!(display_col(&text, 2, tabstop), 4);
+        assert_eq!(display_col(&text, 7, tabstop), 2);
+        assert_eq!(display_col(&text, 10, tabstop), 4);
+        assert_eq!(display_col(&text, 11, tabstop), 8);
+    }
+
+    #[test]
+    fn graphemes_are_taken_whole() {
+        let (accented, family, flag) = ("e\u{301}", "👨\u{200d}👩\u{200d}👧", "🇧🇷");
+        let text = Text::from(format!("{accented}x{family}x{flag}x\r\n"));
+
+        let mut b = 0;
+        for grapheme in [accented, "x", family, "x", flag, "x", "\r\n"] {
+            assert_eq!(grapheme_at(&text, b), grapheme);
+            b += grapheme.len();
+        }
+    }
+}